@@ -0,0 +1,574 @@
+use anyhow::{anyhow, bail, ensure, Context};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use std::{
+    fs::File,
+    io::{Read, Write},
+    path::{Component, Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+use crate::{
+    config::{TempleDirs, CONFIG_FILES},
+    source::SOURCE_FILE,
+    values::Values,
+};
+
+/// Name of the file, at the root of every archive, that describes the
+/// packaged template
+pub const MANIFEST_FILE: &str = "manifest.tpl";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    TarGz,
+    Zip,
+}
+
+impl Format {
+    /// Guesses the archive format from the extension of `path`
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Err`] if the extension is not one of `.tar.gz`, `.tgz`
+    /// or `.zip`
+    #[allow(clippy::case_sensitive_file_extension_comparisons)]
+    pub fn from_path(path: &Path) -> anyhow::Result<Self> {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Ok(Format::TarGz)
+        } else if name.ends_with(".zip") {
+            Ok(Format::Zip)
+        } else {
+            bail!(
+                "Unknown archive format for {}, expected a .tar.gz, .tgz or .zip file",
+                path.display()
+            )
+        }
+    }
+}
+
+/// A file or directory inside an archive, with its path relative to the
+/// archive root
+struct Entry {
+    path: PathBuf,
+    mode: Option<u32>,
+    contents: Option<Vec<u8>>,
+}
+
+impl Entry {
+    fn is_dir(&self) -> bool {
+        self.contents.is_none()
+    }
+
+    /// Path with `/` separators, as stored inside archives
+    fn archive_path(&self) -> String {
+        self.path
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+/// Packages the template at `template` as `name` into the archive `to`,
+/// the format is chosen from the extension of `to`.
+///
+/// Source metadata and the `.git` directory of installed templates are not
+/// packaged.
+///
+/// # Errors
+///
+/// Returns an [`Err`] if the format is unknown or any IO error occurs
+pub fn export(template: &Path, name: &str, to: &Path) -> anyhow::Result<()> {
+    let format = Format::from_path(to)?;
+
+    let mut entries = vec![Entry {
+        path: PathBuf::from(MANIFEST_FILE),
+        mode: Some(0o644),
+        contents: Some(
+            format!(
+                "name: String = \"{name}\"\ntemple_version: String = \"{}\"\n",
+                env!("CARGO_PKG_VERSION")
+            )
+            .into_bytes(),
+        ),
+    }];
+
+    for entry in WalkDir::new(template)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| {
+            let file_name = e.file_name().to_str().unwrap_or_default();
+            !(e.depth() == 1 && (file_name == ".git" || file_name == SOURCE_FILE))
+        })
+    {
+        let entry = entry?;
+        let path = Path::new(name).join(
+            entry
+                .path()
+                .strip_prefix(template)
+                .map_err(|err| anyhow!("Failed stripping prefix: {err}"))?,
+        );
+
+        let contents = if entry.file_type().is_dir() {
+            None
+        } else if entry.file_type().is_file() {
+            Some(
+                std::fs::read(entry.path())
+                    .map_err(|err| anyhow!("Failed reading {}: {err}", entry.path().display()))?,
+            )
+        } else {
+            crate::warn!("Skipping {}, it is not a file", entry.path().display());
+            continue;
+        };
+
+        entries.push(Entry {
+            path,
+            mode: mode_of(&entry.metadata()?),
+            contents,
+        });
+    }
+
+    let file =
+        File::create(to).map_err(|err| anyhow!("Failed creating {}: {err}", to.display()))?;
+
+    match format {
+        Format::TarGz => write_tar_gz(file, &entries),
+        Format::Zip => write_zip(file, &entries),
+    }
+    .with_context(|| format!("Failed writing archive {}", to.display()))
+}
+
+/// Unpacks the template inside `archive` into the directory `into`,
+/// returning the path of the imported template. The template is named
+/// `name` if given, or as recorded in the archive otherwise.
+///
+/// Every entry is checked before anything is written, archives with
+/// entries that would end up outside of the template directory are refused.
+///
+/// # Errors
+///
+/// Returns an [`Err`] if the archive is invalid, a template with the same
+/// name exists and `overwrite` is not set or any IO error occurs
+pub fn import(
+    archive: &Path,
+    into: &Path,
+    name: Option<&str>,
+    overwrite: bool,
+) -> anyhow::Result<PathBuf> {
+    let format = Format::from_path(archive)?;
+    let file = File::open(archive)
+        .map_err(|err| anyhow!("Failed opening {}: {err}", archive.display()))?;
+
+    let entries = match format {
+        Format::TarGz => read_tar_gz(file),
+        Format::Zip => read_zip(file),
+    }
+    .with_context(|| format!("Failed reading archive {}", archive.display()))?;
+
+    for entry in &entries {
+        let mut components = entry.path.components().peekable();
+
+        ensure!(
+            components.peek().is_some()
+                && components.all(|c| matches!(c, Component::Normal(_) | Component::CurDir)),
+            "Refusing to import archive, entry '{}' points outside of the template directory",
+            entry.path.display()
+        );
+    }
+
+    let manifest = entries
+        .iter()
+        .find(|e| e.path == Path::new(MANIFEST_FILE))
+        .and_then(|e| e.contents.as_deref())
+        .ok_or(anyhow!("The archive has no {MANIFEST_FILE}"))?;
+    let manifest =
+        std::str::from_utf8(manifest).map_err(|err| anyhow!("Invalid {MANIFEST_FILE}: {err}"))?;
+    let manifest = Values::from_str(manifest, Path::new(MANIFEST_FILE))?;
+
+    let archived = manifest
+        .value_map
+        .get("name")
+        .and_then(|v| v.as_str())
+        .ok_or(anyhow!("Missing 'name' in {MANIFEST_FILE}"))?;
+
+    let is_valid = |name: &str| {
+        name.is_ascii()
+            && !name.contains(':')
+            && matches!(
+                Path::new(name).components().collect::<Vec<_>>().as_slice(),
+                [Component::Normal(_)]
+            )
+    };
+
+    ensure!(
+        is_valid(archived),
+        "Invalid template name '{archived}' in {MANIFEST_FILE}"
+    );
+
+    ensure!(
+        entries.iter().any(|e| CONFIG_FILES
+            .iter()
+            .any(|config| e.path == Path::new(archived).join(config))),
+        "The archive has no {archived}/config.tpl, it is not a template"
+    );
+
+    let name = name.unwrap_or(archived);
+    ensure!(is_valid(name), "Invalid template name '{name}'");
+
+    let target = into.join(name);
+
+    ensure!(
+        !target.exists() || overwrite,
+        "A template with the name '{name}' already exists at {}",
+        target.display()
+    );
+
+    // Unpack next to the target and move it into place once everything has
+    // been written
+    let staging = into.join(format!(".{name}.import"));
+
+    if staging.exists() {
+        TempleDirs::remove_path(&staging)?;
+    }
+
+    let unpacked = unpack(&entries, archived, &staging);
+
+    if let Err(err) = unpacked {
+        if staging.exists() {
+            TempleDirs::remove_path(&staging)?;
+        }
+
+        return Err(err);
+    }
+
+    if target.exists() {
+        crate::warn!("Overwriting existing template at {}", target.display());
+        TempleDirs::remove_path(&target)?;
+    }
+
+    std::fs::rename(&staging, &target)
+        .map_err(|err| anyhow!("Failed moving template into {}: {err}", target.display()))?;
+
+    Ok(target)
+}
+
+fn unpack(entries: &[Entry], name: &str, at: &Path) -> anyhow::Result<()> {
+    std::fs::create_dir_all(at)?;
+
+    for entry in entries {
+        if entry.path == Path::new(MANIFEST_FILE) {
+            continue;
+        }
+
+        let relative = entry.path.strip_prefix(name).map_err(|_| {
+            anyhow!(
+                "Entry '{}' is outside of the template directory '{name}'",
+                entry.path.display()
+            )
+        })?;
+
+        let path = at.join(relative);
+
+        if let Some(ref contents) = entry.contents {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            std::fs::write(&path, contents)
+                .map_err(|err| anyhow!("Failed writing {}: {err}", path.display()))?;
+        } else {
+            std::fs::create_dir_all(&path)?;
+        }
+
+        #[cfg(unix)]
+        if let Some(mode) = entry.mode {
+            use std::os::unix::fs::PermissionsExt;
+
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode & 0o777))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_tar_gz(file: File, entries: &[Entry]) -> anyhow::Result<()> {
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+    for entry in entries {
+        let mut header = tar::Header::new_gnu();
+        let contents = entry.contents.as_deref().unwrap_or_default();
+
+        header.set_entry_type(if entry.is_dir() {
+            tar::EntryType::Directory
+        } else {
+            tar::EntryType::Regular
+        });
+        header.set_mode(
+            entry
+                .mode
+                .unwrap_or(if entry.is_dir() { 0o755 } else { 0o644 }),
+        );
+        header.set_size(contents.len() as u64);
+
+        builder.append_data(&mut header, entry.archive_path(), contents)?;
+    }
+
+    builder.into_inner()?.finish()?;
+
+    Ok(())
+}
+
+fn read_tar_gz(file: File) -> anyhow::Result<Vec<Entry>> {
+    let mut archive = tar::Archive::new(GzDecoder::new(file));
+    let mut entries = Vec::new();
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+
+        let contents = match entry.header().entry_type() {
+            tar::EntryType::Directory => None,
+            tar::EntryType::Regular => {
+                let mut contents = Vec::new();
+                entry.read_to_end(&mut contents)?;
+                Some(contents)
+            }
+            kind => bail!(
+                "Unsupported entry '{}' of type {kind:?}, only files and directories are allowed",
+                path.display()
+            ),
+        };
+
+        entries.push(Entry {
+            path,
+            mode: entry.header().mode().ok(),
+            contents,
+        });
+    }
+
+    Ok(entries)
+}
+
+fn write_zip(file: File, entries: &[Entry]) -> anyhow::Result<()> {
+    let mut zip = zip::ZipWriter::new(file);
+
+    for entry in entries {
+        let mut options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated);
+
+        if let Some(mode) = entry.mode {
+            options = options.unix_permissions(mode);
+        }
+
+        if let Some(ref contents) = entry.contents {
+            zip.start_file(entry.archive_path(), options)?;
+            zip.write_all(contents)?;
+        } else {
+            zip.add_directory(entry.archive_path(), options)?;
+        }
+    }
+
+    zip.finish()?;
+
+    Ok(())
+}
+
+fn read_zip(file: File) -> anyhow::Result<Vec<Entry>> {
+    let mut archive = zip::ZipArchive::new(file)?;
+    let mut entries = Vec::new();
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let path = PathBuf::from(file.name());
+
+        ensure!(
+            !file.is_symlink(),
+            "Unsupported symlink entry '{}', only files and directories are allowed",
+            path.display()
+        );
+
+        let contents = if file.is_dir() {
+            None
+        } else {
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents)?;
+            Some(contents)
+        };
+
+        entries.push(Entry {
+            path,
+            mode: file.unix_mode(),
+            contents,
+        });
+    }
+
+    Ok(entries)
+}
+
+#[cfg(unix)]
+#[allow(clippy::unnecessary_wraps)]
+fn mode_of(metadata: &std::fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+
+    Some(metadata.permissions().mode() & 0o777)
+}
+
+#[cfg(not(unix))]
+fn mode_of(_: &std::fs::Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{export, import, MANIFEST_FILE};
+    use flate2::{write::GzEncoder, Compression};
+    use std::{io::Write, path::Path};
+
+    const MANIFEST: &[u8] = b"name: String = \"tpl\"";
+
+    /// Writes a tar.gz with a valid template and the entry `path`, of type
+    /// `kind`, that is appended without validating its path
+    fn tar_gz(to: &Path, path: &str, kind: tar::EntryType) {
+        let file = std::fs::File::create(to).expect("Failed creating archive");
+        let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+
+        let mut append = |path: &str, kind: tar::EntryType, contents: &[u8]| {
+            let mut header = tar::Header::new_gnu();
+            header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+            header.set_entry_type(kind);
+            header.set_mode(0o644);
+            header.set_size(contents.len() as u64);
+            if kind == tar::EntryType::Symlink {
+                header.set_link_name("/etc/passwd").expect("Valid link");
+            }
+            header.set_cksum();
+            builder.append(&header, contents).expect("Failed appending");
+        };
+
+        append(MANIFEST_FILE, tar::EntryType::Regular, MANIFEST);
+        append("tpl/config.tpl", tar::EntryType::Regular, b"");
+        append(path, kind, b"x");
+
+        builder
+            .into_inner()
+            .expect("Failed writing archive")
+            .finish()
+            .expect("Failed writing archive");
+    }
+
+    /// Writes a zip with a valid template and the entry `path`, which is a
+    /// symlink if `symlink`
+    fn zip(to: &Path, path: &str, symlink: bool) {
+        let file = std::fs::File::create(to).expect("Failed creating archive");
+        let mut zip = zip::ZipWriter::new(file);
+        let options = zip::write::SimpleFileOptions::default();
+
+        for (path, contents) in [(MANIFEST_FILE, MANIFEST), ("tpl/config.tpl", b"")] {
+            zip.start_file(path, options).expect("Failed adding file");
+            zip.write_all(contents).expect("Failed writing file");
+        }
+
+        if symlink {
+            zip.add_symlink(path, "/etc/passwd", options)
+                .expect("Failed adding symlink");
+        } else {
+            zip.start_file(path, options).expect("Failed adding file");
+            zip.write_all(b"x").expect("Failed writing file");
+        }
+
+        zip.finish().expect("Failed writing archive");
+    }
+
+    #[test]
+    fn refuses_unsafe_entries() {
+        let dir = tempfile::tempdir().expect("Failed creating temp dir");
+        let into = dir.path().join("templates");
+        std::fs::create_dir(&into).expect("Failed creating dir");
+
+        let outside = "points outside of the template directory";
+        let archives = [
+            ("tar.gz", "../x", false, outside),
+            ("tgz", "tpl/../../x", false, outside),
+            ("tar.gz", "/tmp/x", false, outside),
+            ("tar.gz", "tpl/link", true, "of type Symlink"),
+            ("zip", "../x", false, outside),
+            ("zip", "/tmp/x", false, outside),
+            ("zip", "tpl/link", true, "Unsupported symlink entry"),
+        ];
+
+        for (i, (extension, path, symlink, reason)) in archives.into_iter().enumerate() {
+            let archive = dir.path().join(format!("{i}.{extension}"));
+
+            if extension == "zip" {
+                zip(&archive, path, symlink);
+            } else if symlink {
+                tar_gz(&archive, path, tar::EntryType::Symlink);
+            } else {
+                tar_gz(&archive, path, tar::EntryType::Regular);
+            }
+
+            let err = import(&archive, &into, None, false).expect_err(path);
+            let err = format!("{err:#}");
+            assert!(
+                err.contains(&format!("'{path}'")) && err.contains(reason),
+                "{err}"
+            );
+
+            assert_eq!(std::fs::read_dir(&into).expect("Readable dir").count(), 0);
+            assert!(!dir.path().join("x").exists());
+        }
+    }
+
+    #[test]
+    fn overwrite() {
+        let dir = tempfile::tempdir().expect("Failed creating temp dir");
+        let template = dir.path().join("template");
+        let into = dir.path().join("templates");
+        std::fs::create_dir_all(template.join("sub")).expect("Failed creating dir");
+        std::fs::create_dir(&into).expect("Failed creating dir");
+        std::fs::write(template.join("config.tpl"), "name = \"new\"").expect("Failed writing");
+        std::fs::write(template.join("sub/file"), "{{ name }}").expect("Failed writing");
+
+        for format in ["tar.gz", "zip"] {
+            let archive = dir.path().join(format!("tpl.{format}"));
+            export(&template, "tpl", &archive).expect("Failed exporting");
+
+            let existing = into.join("tpl");
+            std::fs::create_dir_all(&existing).expect("Failed creating dir");
+            std::fs::write(existing.join("config.tpl"), "name = \"old\"").expect("Failed writing");
+
+            assert!(import(&archive, &into, None, false).is_err());
+            assert_eq!(
+                std::fs::read_to_string(existing.join("config.tpl")).expect("Failed reading"),
+                "name = \"old\""
+            );
+
+            let path = import(&archive, &into, None, true).expect("Failed importing");
+            assert_eq!(path, existing);
+            assert_eq!(
+                std::fs::read_to_string(existing.join("config.tpl")).expect("Failed reading"),
+                "name = \"new\""
+            );
+            assert_eq!(
+                std::fs::read_to_string(existing.join("sub/file")).expect("Failed reading"),
+                "{{ name }}"
+            );
+
+            // Imported under another name the archived one is left alone
+            let renamed =
+                import(&archive, &into, Some("renamed"), false).expect("Failed importing");
+            assert_eq!(renamed, into.join("renamed"));
+            assert!(renamed.join("sub/file").is_file());
+            assert!(import(&archive, &into, Some("../renamed"), false).is_err());
+
+            std::fs::remove_dir_all(&existing).expect("Failed removing");
+            std::fs::remove_dir_all(&renamed).expect("Failed removing");
+        }
+    }
+}
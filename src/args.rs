@@ -33,6 +33,11 @@ pub enum Commands {
         #[clap(default_value = "")]
         cli_keys: Vec<String>,
 
+        /// Files with values in JSON, YAML or TOML, or in the config syntax.
+        /// Keys from the terminal take precedence over them
+        #[clap(long = "values", value_name = "PATH")]
+        values_files: Vec<PathBuf>,
+
         /// Prefer local (./.temple/template_name) if available [default: prefer ~/.temple/template_name]
         #[clap(long, short)]
         local: bool,
@@ -44,6 +49,18 @@ pub enum Commands {
         /// Overwrite any already existing files
         #[clap(long, short)]
         overwrite: bool,
+
+        /// Don't run the pre and post generation hooks of the template
+        #[clap(long)]
+        no_hooks: bool,
+
+        /// Run the hooks of the template without asking for confirmation
+        #[clap(long, conflicts_with = "no_hooks")]
+        trust: bool,
+
+        /// Number of files rendered in parallel [default: number of CPUs]
+        #[clap(long, short)]
+        jobs: Option<std::num::NonZeroUsize>,
     },
     /// Create a new empty template. You can then place files in it
     Create {
@@ -56,6 +73,72 @@ pub enum Commands {
         /// Name of the template
         template_name: String,
     },
+    /// Install a template from a git repository into the global config dir
+    Add {
+        /// Url of the git repository. Local paths and file:// urls are also accepted
+        url: String,
+
+        /// Name of the template [default: the repository name]
+        #[clap(long, short)]
+        name: Option<String>,
+
+        /// Branch, tag or commit to check out
+        #[clap(long, short)]
+        rev: Option<String>,
+    },
+    /// Update templates installed with `add` from their recorded source
+    Update {
+        /// Name of the template [default: all installed templates]
+        template_name: Option<String>,
+
+        /// Branch, tag or commit to pin the template to
+        #[clap(long, short, requires = "template_name")]
+        rev: Option<String>,
+
+        /// Discard any local edits made to the template
+        #[clap(long, short)]
+        force: bool,
+    },
+    /// Search the templates listed in the registry index
+    Search {
+        /// Text to look for in names, descriptions and tags [default: list all]
+        query: Option<String>,
+
+        /// Path or http(s) url of the index [default: `temple_registry` in the global config]
+        #[clap(long)]
+        index: Option<String>,
+    },
+    /// Install a template listed in the registry index into the global config dir
+    Install {
+        /// Name of the template in the index
+        template_name: String,
+
+        /// Path or http(s) url of the index [default: `temple_registry` in the global config]
+        #[clap(long)]
+        index: Option<String>,
+    },
+    /// Package a template as a .tar.gz or .zip archive
+    Export {
+        /// Name of the template
+        template_name: String,
+
+        /// Path of the archive [default: NAME.tar.gz]
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Install a template from a .tar.gz or .zip archive made with `export`
+    Import {
+        /// Path of the archive
+        archive: PathBuf,
+
+        /// Import into the local temple folder instead of the global one
+        #[clap(long, short)]
+        local: bool,
+
+        /// Overwrite a template with the same name if it exists
+        #[clap(long, short)]
+        overwrite: bool,
+    },
     /// List existing templates
     List {
         /// Show templates in a single space separated list
@@ -67,11 +150,30 @@ pub enum Commands {
         /// Don't show error messages
         #[clap(long, short)]
         errors: bool,
+        /// Format of the output
+        #[clap(long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Check a template for errors without rendering it
+    Check {
+        /// Name of the template
+        template_name: String,
     },
     /// Parse and dump objects to stdout
     DebugConfig {
         /// The path to the configuration file
         paths: Vec<PathBuf>,
+        /// Format of the output
+        #[clap(long, value_enum, default_value_t)]
+        format: OutputFormat,
+    },
+    /// Format config files canonically, keeping their comments
+    Fmt {
+        /// Config files or template directories [default: the configs of every template]
+        paths: Vec<PathBuf>,
+        /// Don't write the files, fail if any is not formatted
+        #[clap(long)]
+        check: bool,
     },
     /// Initialize temple configuration directory
     Init {
@@ -87,9 +189,22 @@ pub enum Commands {
     Info {
         /// Name of the template
         template_name: String,
+        /// Format of the output
+        #[clap(long, value_enum, default_value_t)]
+        format: OutputFormat,
     },
 }
 
+/// Format of the output of the commands that show information
+#[derive(clap::ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Text for humans
+    #[default]
+    Text,
+    /// JSON for scripts and editors, with stable keys
+    Json,
+}
+
 #[derive(Debug, Subcommand, Clone, Copy, PartialEq)]
 pub enum InitOpt {
     /// Create the global temple configuration dir
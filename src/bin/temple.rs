@@ -1,33 +1,32 @@
 use anyhow::{anyhow, bail, ensure, Context, Result};
 use clap::Parser;
-use inquire::validator::Validation;
+use owo_colors::OwoColorize;
 use std::{
-    borrow::Cow,
     env::{current_dir, current_exe},
-    fs::OpenOptions,
     io::{Read, Write},
-    ops::Not,
+    ops::Range,
     path::PathBuf,
     process::ExitCode,
-    str::FromStr,
 };
 use temple::{
-    args::{Args, Commands, InitOpt},
-    config::{Prefer, TempleDirs},
-    error, info,
-    replacer::ContentsLexer,
+    archive,
+    args::{Args, Commands, InitOpt, OutputFormat},
+    check::{self, Severity},
+    config::{Prefer, Template, TempleDirs},
+    error,
+    error::{ConfigError, Location},
+    hooks, info,
+    prompt::{self, Prompter, Terminal},
+    registry::{self, Index},
+    render::{Options, Renderer},
+    source::{self, Source},
     trace,
-    values::{Type, Values},
+    values::{self, Format, Values},
     warn,
 };
-use walkdir::WalkDir;
 
 fn templ_path(path: &std::path::Path) -> PathBuf {
-    if path.join("config.tpl").exists() {
-        path.join("config.tpl")
-    } else {
-        path.join("config.temple")
-    }
+    Template::config_file(path).unwrap_or_else(|| path.join("config.tpl"))
 }
 
 fn name_is_valid(name: &str) -> Result<()> {
@@ -36,6 +35,59 @@ fn name_is_valid(name: &str) -> Result<()> {
         .ok_or(anyhow!("Invalid name: '{name}'"))
 }
 
+/// Carets under the columns of the line, clamped to its length
+struct Underlined(Range<usize>, usize);
+
+impl std::fmt::Display for Underlined {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let start = self.0.start.clamp(1, self.1.max(1));
+        let end = self.0.end.min(self.1 + 1).max(start + 1);
+
+        write!(
+            f,
+            "{: >pad$}{}",
+            "",
+            "^".repeat(end - start)
+                .if_supports_color(owo_colors::Stream::Stdout, |s| {
+                    s.style(owo_colors::Style::new().bold().yellow())
+                }),
+            pad = start - 1,
+        )
+    }
+}
+
+/// Formats `message` followed by the line of `location` with the error underlined
+fn diagnostic(message: &str, location: &Location) -> String {
+    let blue = owo_colors::Style::new().bold().blue();
+    // The gutter fits the line number
+    let width = location.line.to_string().len().max(3);
+
+    format!(
+        "{message}\n{: >width$}{arrow}{location}\n{empty_pipe}\n{bline} {contents}\n{empty_pipe} {underline}",
+        "",
+        arrow = "--> ".if_supports_color(owo_colors::Stream::Stdout, |s| s.style(blue)),
+        empty_pipe = format!("{: >width$} |", "")
+            .if_supports_color(owo_colors::Stream::Stdout, |s| s.style(blue)),
+        bline = format!("{line: >width$} |", line = location.line)
+            .if_supports_color(owo_colors::Stream::Stdout, |s| s.style(blue)),
+        contents = location.source,
+        underline = Underlined(location.columns.clone(), location.source.chars().count())
+    )
+}
+
+fn config_error(err: &ConfigError) -> String {
+    match err.location() {
+        Some(location) => diagnostic(&err.message(), location),
+        None => err.message(),
+    }
+}
+
+fn print_config_errors(errors: &[ConfigError]) {
+    for err in errors {
+        error!(config_error(err));
+    }
+}
+
 fn parse_values_from_path(path: &std::path::Path, buff: &mut String) -> Result<Values> {
     buff.clear();
 
@@ -47,22 +99,48 @@ fn parse_values_from_path(path: &std::path::Path, buff: &mut String) -> Result<V
     file.read_to_string(buff)
         .map_err(|err| anyhow!("Error reading file {path}: {err}", path = path.display()))?;
 
-    Values::from_str(buff, path).map_err(|err| {
-        eprintln!("{err:?}");
+    match Format::from_path(path) {
+        Some(format) => Values::from_data(buff, path, format),
+        None => Values::from_str_with_warnings(buff, path).map(print_config_warnings),
+    }
+    .map_err(|err| {
+        error!(config_error(&err));
         anyhow!("Failed to parse values from {}", path.display())
     })
 }
 
-fn parse_values_from_str(str: &str, desc: &str) -> Result<Values> {
-    Values::from_str(str, current_exe().unwrap().as_path()).map_err(|err| {
-        eprintln!("{err:?}");
-        anyhow!("Failed to parse values from {desc}")
+/// Prints the problems found parsing a config that did not stop it
+fn print_config_warnings((values, warnings): (Values, Vec<ConfigError>)) -> Values {
+    for warning in &warnings {
+        warn!(config_error(warning));
+    }
+
+    values
+}
+
+/// Parses the files given with `--values`, the values of each file
+/// overriding the ones of the files before it
+fn parse_values_files(paths: &[PathBuf]) -> Result<Values> {
+    let mut buff = String::new();
+
+    paths.iter().try_fold(Values::default(), |config, path| {
+        Ok(config.stash(parse_values_from_path(path, &mut buff)?))
     })
 }
 
+fn parse_values_from_str(str: &str, desc: &str) -> Result<Values> {
+    Values::from_str_with_warnings(str, current_exe().unwrap().as_path())
+        .map(print_config_warnings)
+        .map_err(|err| {
+            error!(config_error(&err));
+            anyhow!("Failed to parse values from {desc}")
+        })
+}
+
 fn app(args: &Args) -> Result<()> {
     let temple_dirs = TempleDirs::default_paths()
         .map_err(|e| anyhow!("Failed getting default directories: {e}"))?;
+    let mut prompter = Terminal;
 
     trace!("Global config: {}", temple_dirs.global_config().display());
     trace!(
@@ -92,7 +170,10 @@ If this is your first temple execution you can create a new global config with t
 
     match args.command {
         Commands::List { .. } => temple_dirs.display_available_templates(&args.command),
-        Commands::Info { ref template_name } => {
+        Commands::Info {
+            ref template_name,
+            format,
+        } => {
             let is_global = !template_name.starts_with("local:");
             let name = template_name.trim_start_matches("local:");
 
@@ -118,10 +199,57 @@ If this is your first temple execution you can create a new global config with t
             let config = parse_values_from_path(&path, &mut buffer)
                 .map_err(|err| anyhow!("Error while parsing config: {err}"))?;
 
+            if format == OutputFormat::Json {
+                // The values of the template override the ones of these
+                let shadowed = [
+                    Some(temple_dirs.global_config()),
+                    temple_dirs.local_config(),
+                ]
+                .into_iter()
+                .flatten()
+                .filter_map(Template::config_file)
+                .map(|path| Ok((parse_values_from_path(&path, &mut buffer)?, path)))
+                .collect::<Result<Vec<_>>>()?;
+
+                let mut keys = config.to_json();
+                for (key, entry) in &mut keys {
+                    let overrides = shadowed
+                        .iter()
+                        .filter(|(values, _)| values.value_map.contains_key(key))
+                        .map(|(_, path)| path)
+                        .collect::<Vec<_>>();
+                    entry["overrides"] = serde_json::json!(overrides);
+                }
+
+                let scope = if templates.local.iter().any(|local| local.0 == template.0) {
+                    "local"
+                } else {
+                    "global"
+                };
+
+                println!(
+                    "{:#}",
+                    serde_json::json!({
+                        "name": name,
+                        "scope": scope,
+                        "path": template.0,
+                        "config": path,
+                        "source": template.source(),
+                        "keys": keys,
+                    })
+                );
+
+                return Ok(());
+            }
+
             println!(
-                "Name: {name}\nPath: {path}\nConfig: {config}\nConfig values: {conf:#?}",
+                "Name: {name}\nPath: {path}\nConfig: {config}{source}\nConfig values: {conf:#?}",
                 path = template.0.display(),
                 config = path.display(),
+                source = template
+                    .source()
+                    .map(|source| format!("\nSource: {source}"))
+                    .unwrap_or_default(),
                 conf = config
             );
 
@@ -181,7 +309,9 @@ If this is your first temple execution you can create a new global config with t
 
             // To avoid the user doing unwanted operations we prompt for confirmation with
             // the path visible to the user
-            if !is_global && !confirm_creation(&path)? {
+            if !is_global
+                && !prompter.confirm(&format!("Do you want to create {}?", path.display()))?
+            {
                 return Ok(());
             }
 
@@ -205,6 +335,199 @@ If this is your first temple execution you can create a new global config with t
 
             Ok(())
         }
+        Commands::Add {
+            ref url,
+            ref name,
+            ref rev,
+        } => {
+            let name = name
+                .as_deref()
+                .or_else(|| source::name_from_url(url))
+                .ok_or(anyhow!(
+                    "Failed getting a template name from '{url}', set one with --name"
+                ))?;
+
+            install_from_git(temple_dirs.global_config(), url, name, rev.as_deref())
+        }
+        Commands::Update {
+            ref template_name,
+            ref rev,
+            force,
+        } => {
+            let templates = temple_dirs
+                .get_available_templates()
+                .map_err(|err| anyhow!("Failed to get templates: {err}"))?;
+
+            let targets = if let Some(template_name) = template_name {
+                let name = template_name.trim_start_matches("local:");
+                name_is_valid(name)?;
+
+                let prefers = if template_name.starts_with("local:") {
+                    Prefer::Local
+                } else {
+                    Prefer::Global
+                };
+
+                let template = templates
+                    .get_named(name, &prefers)
+                    .ok_or(anyhow!("Template '{name}' does not exist"))?;
+
+                ensure!(
+                    template.source().is_some(),
+                    "Template '{name}' was not installed from a source"
+                );
+
+                vec![template]
+            } else {
+                templates
+                    .global
+                    .iter()
+                    .chain(templates.local.iter())
+                    .filter(|t| t.source().is_some())
+                    .collect()
+            };
+
+            if targets.is_empty() {
+                info!("There are no installed templates to update");
+            }
+
+            for template in targets {
+                update_template(template, rev.as_deref(), force)?;
+            }
+
+            Ok(())
+        }
+        Commands::Export {
+            ref template_name,
+            ref output,
+        } => {
+            let name = template_name.trim_start_matches("local:");
+            name_is_valid(name)?;
+
+            let prefers = if template_name.starts_with("local:") {
+                Prefer::Local
+            } else {
+                Prefer::Global
+            };
+
+            let templates = temple_dirs
+                .get_available_templates()
+                .map_err(|err| anyhow!("Failed to get templates: {err}"))?;
+
+            let template = templates
+                .get_named(name, &prefers)
+                .ok_or(anyhow!("Template '{name}' does not exist"))?;
+
+            let output = output
+                .clone()
+                .unwrap_or_else(|| PathBuf::from(format!("{name}.tar.gz")));
+
+            info!("Exporting template '{name}' into {}", output.display());
+
+            archive::export(&template.0, name, &output)
+                .map_err(|err| anyhow!("Failed exporting template '{name}': {err:#}"))
+        }
+        Commands::Import {
+            ref archive,
+            local,
+            overwrite,
+        } => {
+            let into = if !local {
+                temple_dirs.global_config()
+            } else if let Some(path) = temple_dirs.local_config() {
+                path
+            } else {
+                bail!("Tried importing a local template but there is no local temple folder");
+            };
+
+            info!("Importing template from {}", archive.display());
+
+            let path = archive::import(archive, into, None, overwrite)
+                .map_err(|err| anyhow!("Failed importing {}: {err:#}", archive.display()))?;
+
+            info!("Imported template at {}", path.display());
+
+            Ok(())
+        }
+        Commands::Search {
+            ref query,
+            ref index,
+        } => {
+            let index = Index::load(&registry_location(&temple_dirs, index.as_deref())?)?;
+            let found = index.search(query.as_deref().unwrap_or_default());
+
+            if found.is_empty() {
+                info!("No templates found");
+            }
+
+            for entry in found {
+                println!(
+                    "{name}{description}{tags}",
+                    name = entry.name,
+                    description = if entry.description.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" - {}", entry.description)
+                    },
+                    tags = if entry.tags.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" [{}]", entry.tags.join(", "))
+                    },
+                );
+            }
+
+            Ok(())
+        }
+        Commands::Install {
+            ref template_name,
+            ref index,
+        } => {
+            let index = Index::load(&registry_location(&temple_dirs, index.as_deref())?)?;
+            let entry = index
+                .get(template_name)
+                .ok_or(anyhow!("Template '{template_name}' is not in the index"))?;
+
+            if !entry.is_archive() {
+                return install_from_git(
+                    temple_dirs.global_config(),
+                    &entry.source,
+                    template_name,
+                    entry.rev.as_deref(),
+                );
+            }
+
+            let archive = if registry::is_http(&entry.source) {
+                let name = entry.source.rsplit('/').next().unwrap_or_default();
+                let path =
+                    std::env::temp_dir().join(format!("temple-{}-{name}", std::process::id()));
+
+                info!("Downloading {}", entry.source);
+                registry::download(&entry.source, &path)?;
+
+                path
+            } else {
+                PathBuf::from(entry.source.trim_start_matches("file://"))
+            };
+
+            let imported = archive::import(
+                &archive,
+                temple_dirs.global_config(),
+                Some(template_name),
+                false,
+            );
+
+            if registry::is_http(&entry.source) {
+                _ = std::fs::remove_file(&archive);
+            }
+
+            let path = imported
+                .map_err(|err| anyhow!("Failed installing template '{template_name}': {err:#}"))?;
+
+            info!("Installed template '{template_name}' at {}", path.display());
+
+            Ok(())
+        }
         Commands::Remove { ref template_name } => {
             let is_local = template_name.starts_with("local:");
             let name = template_name.trim_start_matches("local:");
@@ -227,7 +550,7 @@ If this is your first temple execution you can create a new global config with t
                 name
             );
 
-            if !confirm_remove(&path)? {
+            if !confirm_remove(&mut prompter, &path)? {
                 return Ok(());
             }
 
@@ -241,7 +564,7 @@ If this is your first temple execution you can create a new global config with t
             temple::args::DeinitOpt::Global => {
                 let path = temple_dirs.global_config();
 
-                if confirm_remove(path)? {
+                if confirm_remove(&mut prompter, path)? {
                     info!(
                         "Removing temple configuration directory: {}",
                         path.display()
@@ -253,7 +576,7 @@ If this is your first temple execution you can create a new global config with t
             }
             temple::args::DeinitOpt::Local => {
                 if let Some(path) = temple_dirs.local_config() {
-                    if confirm_remove(path)? {
+                    if confirm_remove(&mut prompter, path)? {
                         info!(
                             "Removing temple configuration directory: {}",
                             path.display()
@@ -267,45 +590,148 @@ If this is your first temple execution you can create a new global config with t
                 }
             }
         },
-        Commands::DebugConfig { ref paths } => {
+        Commands::Check { ref template_name } => {
+            let name = template_name.trim_start_matches("local:");
+            name_is_valid(name)?;
+
+            let prefers = if template_name.starts_with("local:") {
+                Prefer::Local
+            } else {
+                Prefer::Global
+            };
+
+            let templates = temple_dirs
+                .get_available_templates()
+                .map_err(|err| anyhow!("Failed to get templates: {err}"))?;
+
+            let template = templates
+                .get_named(name, &prefers)
+                .ok_or(anyhow!("Template '{name}' does not exist"))?;
+
+            check_template(
+                template,
+                temple_dirs.global_config(),
+                temple_dirs.local_config(),
+            )
+        }
+        Commands::DebugConfig {
+            ref paths,
+            format: OutputFormat::Json,
+        } => debug_config_json(paths),
+        Commands::DebugConfig { ref paths, .. } => {
             let mut result_value = Values::default();
 
             for path in paths {
                 info!("Reading: {}", path.display());
 
-                let contents = std::fs::read_to_string(path)?;
-
-                let values = Values::from_str(&contents, path).map_err(|err| {
-                    eprintln!("{err:?}");
-                    anyhow!("Failed to parse values from {}", path.display())
-                })?;
+                let values = parse_values_from_path(path, &mut String::new())?;
 
                 info!("{}:\n{:#?}", path.display(), values);
 
-                values.verify_types().map_err(|err| {
-                    error!(err);
+                values.verify_types().map_err(|errors| {
+                    print_config_errors(&errors);
                     anyhow!("Invalid types")
                 })?;
 
                 result_value = result_value.stash(values);
-                result_value.verify_types().map_err(|err| {
-                    error!(err);
+                result_value.verify_types().map_err(|errors| {
+                    print_config_errors(&errors);
                     anyhow!("Invalid types")
                 })?;
             }
 
             info!("End result:\n{:#?}", result_value);
-            result_value.verify_types().map_err(|err| {
-                error!(err);
+            result_value.verify_types().map_err(|errors| {
+                print_config_errors(&errors);
                 anyhow!("Invalid types")
             })
         }
+        Commands::Fmt { ref paths, check } => {
+            let files = if paths.is_empty() {
+                let templates = temple_dirs
+                    .get_available_templates()
+                    .map_err(|err| anyhow!("Failed to get templates: {err}"))?;
+
+                [
+                    Some(temple_dirs.global_config()),
+                    temple_dirs.local_config(),
+                ]
+                .into_iter()
+                .flatten()
+                .chain(templates.global.iter().map(|t| t.0.as_path()))
+                .chain(templates.local.iter().map(|t| t.0.as_path()))
+                .filter_map(Template::config_file)
+                .filter(|path| Format::from_path(path).is_none())
+                .collect::<Vec<_>>()
+            } else {
+                paths
+                    .iter()
+                    .map(|path| {
+                        let file = if path.is_dir() {
+                            Template::config_file(path)
+                                .ok_or(anyhow!("There is no config file in {}", path.display()))?
+                        } else {
+                            path.clone()
+                        };
+
+                        ensure!(
+                            Format::from_path(&file).is_none(),
+                            "{} is not written in the config syntax",
+                            file.display()
+                        );
+
+                        Ok(file)
+                    })
+                    .collect::<Result<Vec<_>>>()?
+            };
+
+            let mut failed = 0;
+            let mut unformatted = 0;
+
+            for path in &files {
+                let contents = std::fs::read_to_string(path)
+                    .with_context(|| format!("Error reading file {}", path.display()))?;
+
+                let formatted = match values::format(&contents, path) {
+                    Ok(formatted) => formatted,
+                    Err(err) => {
+                        error!(config_error(&err));
+                        failed += 1;
+                        continue;
+                    }
+                };
+
+                if formatted == contents {
+                    trace!("{} is already formatted", path.display());
+                } else if check {
+                    warn!("{} is not formatted", path.display());
+                    unformatted += 1;
+                } else {
+                    std::fs::write(path, formatted)
+                        .with_context(|| format!("Error writing file {}", path.display()))?;
+                    info!("Formatted {}", path.display());
+                }
+            }
+
+            ensure!(failed == 0, "Failed to format {failed} config files");
+            ensure!(
+                unformatted == 0,
+                "{unformatted} of {} config files are not formatted",
+                files.len()
+            );
+
+            Ok(())
+        }
         Commands::New {
             ref template_name,
             ref project_name,
             mut in_place,
             ref cli_keys,
+            ref values_files,
             ref overwrite,
+            no_hooks,
+            trust,
+            jobs,
             ..
         } => {
             let templates = temple_dirs
@@ -349,44 +775,18 @@ If this is your first temple execution you can create a new global config with t
                         templ_path(&template.0).display()
                     )
                 })?;
+            let file_config = parse_values_files(values_files)?;
             let cli_config = parse_values_from_str(&cli_keys.join(" "), "Args")
                 .map_err(|err| anyhow!("Error while parsing config from str: {err}"))?;
 
             let mut config = global_config
                 .stash(local_config)
                 .stash(template_config)
+                .stash(file_config)
                 .stash(cli_config);
 
-            for (name, value) in config.value_map.iter_mut() {
-                if value.is_null() {
-                    let dtype = config.type_map.get_mut(name).expect("We know it exists");
-                    match dtype {
-                        Type::Array(_)
-                        | Type::Object(_)
-                        | Type::Any
-                        | Type::Number => {
-                            let input = ask_any(name, &format!("{dtype}"), dtype.clone())?;
-                            let input_value = Values::parse_value(&input, "")
-                                .expect("Infallible, checked inside the function");
-
-                            let val_type = Type::from_value(&input_value, dtype);
-                            if val_type.is_equivalent(dtype) {
-                                *value = input_value;
-                                *dtype = val_type;
-                            } else {
-                                bail!("Error, value not valid for key {name:?} with type {dtype}\n");
-                            }
-                        },
-                        Type::String => {
-                            *value = tera::Value::String(ask_string(name)?)
-                        },
-                        Type::Bool => *value = tera::Value::Bool(ask_bool(&format!("Set bool value of {name:?} to `true`?"))?),
-                        Type::Unknown => bail!(
-                            "Keys with unknown data type and no value assigned are not supported: {name:?}\n"
-                        ),
-                    }
-                }
-            }
+            prompt::fill_missing(&mut config, &mut prompter)?;
+
             trace!("Final config: {:?}", config.value_map);
             trace!("Working with template {:?}", template);
 
@@ -428,319 +828,332 @@ If this is your first temple execution you can create a new global config with t
                 );
             }
 
-            let mut overwrite_targets = None;
-
-            let walker = WalkDir::new(&template.0).into_iter();
-            for entry in walker.filter_entry(|e| {
-                let name = e.file_name().to_str().unwrap_or_default();
-                !(name.ends_with(".temple") || name.ends_with(".tpl"))
-            }) {
-                if let Ok(entry) = entry.map_err(|err| warn!("Error with path: {}", err)) {
-                    let target = entry
-                        .path()
-                        .strip_prefix(&template.0)
-                        .map_err(|err| anyhow!("Failed stripping prefix: {err}"))?;
-
-                    if target == PathBuf::from_str("").expect("Infallible") {
-                        trace!(
-                            "Rendering: Skipping empty target, presumably the root file. Path {}",
-                            entry.path().display()
-                        );
-                        continue;
-                    }
+            let pre_hooks = hooks::commands(&config, hooks::PRE_HOOKS)?;
+            let post_hooks = hooks::commands(&config, hooks::POST_HOOKS)?;
 
-                    let target = current_dir.join(target);
+            let run_hooks = if pre_hooks.is_empty() && post_hooks.is_empty() {
+                false
+            } else if no_hooks {
+                info!(
+                    "Skipping {} hooks of the template",
+                    pre_hooks.len() + post_hooks.len()
+                );
+                false
+            } else if trust || confirm_hooks(&mut prompter, &pre_hooks, &post_hooks)? {
+                true
+            } else {
+                warn!("Skipping the hooks of the template");
+                false
+            };
 
-                    trace!(
-                        "Rendering: Render of {} into {}",
-                        entry.path().display(),
-                        target.display()
-                    );
+            if run_hooks {
+                let invoked_at = std::env::current_dir().context("Failed getting current dir")?;
 
-                    if entry.file_type().is_dir() {
-                        continue;
-                    }
+                for hook in &pre_hooks {
+                    info!("Running pre generation hook `{hook}`");
 
-                    let mut origin =
-                        OpenOptions::new()
-                            .read(true)
-                            .open(entry.path())
-                            .map_err(|err| {
-                                anyhow!("Error with origin path {}: {err}", entry.path().display())
-                            })?;
+                    let changes = hooks::run_pre(hook, &invoked_at, &config).map_err(|err| {
+                        anyhow!("Pre generation hook failed, nothing was rendered: {err}")
+                    })?;
 
-                    let target = render_path(&target, &config)?;
+                    if let Some(changes) = changes {
+                        hooks::apply(&mut config, changes).map_err(|err| {
+                            anyhow!("Pre generation hook `{hook}` returned invalid values: {err}")
+                        })?;
+                    }
+                }
+            }
 
-                    // Set the overwrite value once
-                    overwrite_targets = if target.exists() && overwrite_targets.is_none() {
-                        if !overwrite {
-                            Some(ask_bool(&format!("The target dir {} already exists. Do you want to overwrite the target files?", target.display()))?)
-                        } else {
-                            Some(*overwrite)
-                        }
+            let options = Options {
+                jobs,
+                overwrite: Some(Box::new(|target| {
+                    if *overwrite {
+                        Ok(true)
                     } else {
-                        overwrite_targets
-                    };
-
-                    // If overwrite is false skip the render
-                    if target.exists() && !overwrite_targets.is_some_and(|v| v) {
-                        warn!(
-                            "Skipping dir {} because it already exists",
-                            target.display()
-                        );
-                        continue;
+                        prompter.bool(&format!("The target dir {} already exists. Do you want to overwrite the target files?", target.display()))
                     }
+                })),
+            };
 
-                    // Create parent dirs as needed
-                    if let Some(par) = target.parent() {
-                        std::fs::create_dir_all(par).map_err(|err| {
-                            anyhow!("Error while creating parent of {}: {err}", target.display())
-                        })?;
-                    }
+            let report =
+                Renderer::new(&config)?.render_template(&template.0, &current_dir, options)?;
 
-                    let mut target = OpenOptions::new()
-                        .create(true)
-                        .truncate(overwrite_targets.unwrap_or_default())
-                        .write(true)
-                        .open(&target)
-                        .map_err(|err| {
-                            anyhow!("Error with target path {}: {err}", target.display())
-                        })?;
+            for skipped in &report.skipped {
+                warn!(
+                    "Skipping file {} because it already exists",
+                    current_dir.join(skipped).display()
+                );
+            }
 
-                    buff.clear();
-                    origin.read_to_string(&mut buff).map_err(|err| {
-                        anyhow!(
-                            "Error while reading origin path {}: {err}",
-                            entry.path().display()
-                        )
-                    })?;
+            if !report.errors.is_empty() {
+                for err in &report.errors {
+                    error!(diagnostic(&err.message(), err.location()));
+                }
 
-                    let contents = buff.as_str();
-                    let path = entry.path();
-                    let repl = Replaced::from(
-                        &collect_tokens(ContentsLexer::new(contents, path, &config)?),
-                        &config,
-                    )
-                    .map_err(|err| {
-                        anyhow!(
-                            "Error while replacing values from {}: {err:?}",
-                            path.display()
-                        )
-                    })?;
+                bail!(
+                    "Found {} errors while rendering template '{name}', nothing was rendered",
+                    report.errors.len()
+                );
+            }
 
-                    for res in repl.contents {
-                        target
-                            .write_all(res.as_bytes())
-                            .map_err(|err| anyhow!("Error writing: {err}"))?;
-                    }
+            info!("Rendered {:?} at {:?}", name, current_dir.display());
 
-                    // info!("{:?}", repl.map(|v| v.contents.join("")));
-                };
+            if !run_hooks {
+                return Ok(());
+            }
+
+            for hook in &post_hooks {
+                info!("Running post generation hook `{hook}`");
+
+                hooks::run(hook, &current_dir, &config).map_err(|err| {
+                    anyhow!(
+                        "Post generation hook failed: {err}\nThe rendered files were kept at {}",
+                        current_dir.display()
+                    )
+                })?;
             }
 
-            info!("Rendered {:?} at {:?}", name, current_dir.display());
             Ok(())
         }
     }
 }
 
-fn render_path(render: &std::path::Path, config: &Values) -> Result<PathBuf, anyhow::Error> {
-    let contents = render.display().to_string();
-    let path = std::path::Path::new("Path");
-    let repl = Replaced::from(
-        &collect_tokens(ContentsLexer::new(&contents, path, config)?),
-        config,
-    )
-    .map_err(|err| {
-        anyhow!(
-            "Error while replacing values from path name {}: {err:?}",
-            path.display()
-        )
-    })?;
-    let path = repl.contents.join("");
-    let path = PathBuf::from_str(&path).map_err(|err| {
-        anyhow!(
-            "Error computing target path from {}: {err}",
-            render.display()
-        )
-    })?;
-    Ok(path)
-}
+fn install_from_git(
+    into: &std::path::Path,
+    url: &str,
+    name: &str,
+    rev: Option<&str>,
+) -> Result<()> {
+    name_is_valid(name)?;
 
-fn collect_tokens(mut contents: ContentsLexer<'_>) -> Vec<temple::replacer::Type<'_>> {
-    let mut con = vec![];
+    let path = into.join(name);
 
-    while let Some(token) = contents.next() {
-        if let Err(e) = token {
-            error!(e);
-            break;
-        }
+    ensure!(
+        !path.exists(),
+        "A template with the name '{name}' already exists at {}",
+        path.display()
+    );
+
+    info!("Cloning {url} into {}", path.display());
 
-        trace!(
-            "Lexer: {:?}: {}: {}: {token:?}",
-            contents.span(),
-            contents.get_location(contents.span()),
-            contents.slice(),
+    let installed = Source::clone_into(url, rev, &path).and_then(|source| {
+        ensure!(
+            Template::config_file(&path).is_some(),
+            "The repository has no config.tpl, it is not a template"
         );
+        source.write(&path)
+    });
+
+    if let Err(err) = installed {
+        if path.exists() {
+            TempleDirs::remove_path(&path)?;
+        }
 
-        con.push(token.unwrap());
+        bail!("Failed installing template '{name}': {err:#}");
     }
 
-    con
+    info!("Installed template '{name}' at {}", path.display());
+
+    Ok(())
 }
 
-#[derive(Debug, Clone)]
-#[repr(transparent)]
-struct Replaced<'a> {
-    contents: Vec<Cow<'a, str>>,
+/// Returns the location of the registry index, either given explicitly or
+/// set with `temple_registry` in the global config
+fn registry_location(temple_dirs: &TempleDirs, index: Option<&str>) -> Result<String> {
+    if let Some(index) = index {
+        return Ok(index.to_owned());
+    }
+
+    let config =
+        parse_values_from_path(&templ_path(temple_dirs.global_config()), &mut String::new())?;
+
+    config
+        .value_map
+        .get("temple_registry")
+        .and_then(|v| v.as_str())
+        .map(ToOwned::to_owned)
+        .ok_or(anyhow!(
+            "No registry index given, use --index or set 'temple_registry' in the global config"
+        ))
 }
 
-#[derive(Debug, Clone)]
-#[allow(dead_code)]
-enum ErrorReplace<'i> {
-    NoValue(&'i str),
-    NoField(&'i str, &'i str),
-    ExpectedValue(&'i str),
-    UnexpectedObject(&'i str, &'i str),
-    UnexpectedField(&'i str, &'i str),
+/// Lints `template` with the values it would be rendered with, printing
+/// every diagnostic found. Fails if any of them is an error
+fn check_template(
+    template: &Template,
+    global_config: &std::path::Path,
+    local_config: Option<&std::path::Path>,
+) -> Result<()> {
+    let name = template.name();
+    let mut buff = String::new();
+
+    let global_config = parse_values_from_path(&templ_path(global_config), &mut buff)?;
+    let local_config = local_config
+        .map(templ_path)
+        .map(|local| parse_values_from_path(&local, &mut buff))
+        .transpose()?
+        .unwrap_or_default();
+    let template_config = parse_values_from_path(&templ_path(&template.0), &mut buff)?;
+    let own_config = template_config.clone();
+
+    let config = global_config.stash(local_config).stash(template_config);
+
+    config.verify_types().map_err(|errors| {
+        print_config_errors(&errors);
+        anyhow!("Invalid types in the configuration of '{name}'")
+    })?;
+
+    let diagnostics = check::check(&template.0, &config, &own_config)?;
+    let mut errors = 0;
+
+    for diagnostic in &diagnostics {
+        match diagnostic.severity {
+            Severity::Error => {
+                errors += 1;
+                error!(match &diagnostic.location {
+                    Some(location) => self::diagnostic(&diagnostic.message, location),
+                    None => diagnostic.message.clone(),
+                });
+            }
+            Severity::Warning => warn!(diagnostic.message),
+        }
+    }
+
+    ensure!(
+        errors == 0,
+        "Found {errors} errors and {} warnings in template '{name}'",
+        diagnostics.len() - errors
+    );
+
+    info!(
+        "Template '{name}' has no errors and {} warnings",
+        diagnostics.len()
+    );
+
+    Ok(())
 }
 
-impl<'a> Replaced<'a> {
-    fn from(
-        mut value: &[temple::replacer::Type<'a>],
-        values: &'a Values,
-    ) -> Result<Self, Vec<ErrorReplace<'a>>> {
-        use temple::replacer::Type;
+fn update_template(template: &Template, rev: Option<&str>, force: bool) -> Result<()> {
+    let name = template.name();
+    let source = template
+        .source()
+        .expect("Only templates with a source are updated");
 
-        let mut contents = Vec::new();
-        let mut errors = Vec::new();
+    let edits = source::local_changes(&template.0)?;
 
-        while !value.is_empty() {
-            match *value {
-                [Type::Raw(blob), ..] => {
-                    contents.push(Cow::Borrowed(blob));
+    if !edits.is_empty() {
+        ensure!(
+            force,
+            "Template '{name}' has local edits, use --force to discard them:\n    {}",
+            edits.join("\n    ")
+        );
 
-                    value = &value[1..];
-                }
-                [Type::Ident(ident), ..] => {
-                    if let Some(v) = values.value_map.get(ident) {
-                        if let Some(v) = v.as_object().is_none().then_some(v) {
-                            if let tera::Value::String(v) = v {
-                                contents.push(Cow::Owned(v.to_owned()));
-                            } else if v.is_null().not() {
-                                contents.push(Cow::Owned(v.to_string()));
-                            } else {
-                                errors.push(ErrorReplace::NoValue(ident))
-                            }
-                        } else {
-                            errors.push(ErrorReplace::ExpectedValue(ident));
-                        }
-                    } else {
-                        errors.push(ErrorReplace::NoValue(ident));
-                    }
+        warn!(
+            "Discarding local edits of '{name}':\n    {}",
+            edits.join("\n    ")
+        );
+    }
 
-                    value = &value[1..];
-                }
-                [Type::IdentWithField(access), ..] => {
-                    let (ident, fields) = access.split_once('.').expect(
-                        "The REGEX does guarantee there is at least an identifier and one field",
-                    );
+    info!("Fetching '{name}' from {}", source.url);
 
-                    'a: {
-                        if let Some(mut curr) = values.value_map.get(ident) {
-                            for field in fields.split('.') {
-                                curr = if curr.is_object() {
-                                    if let Some(v) = curr.get(field) {
-                                        v
-                                    } else {
-                                        errors.push(ErrorReplace::NoField(access, field));
-                                        break;
-                                    }
-                                } else {
-                                    errors.push(ErrorReplace::UnexpectedField(access, field));
-                                    break;
-                                }
-                            }
-
-                            if curr.is_object() {
-                                errors.push(ErrorReplace::UnexpectedObject(access, ""));
-                                break 'a;
-                            }
-
-                            if let tera::Value::String(curr) = curr {
-                                contents.push(Cow::Owned(curr.to_owned()));
-                            } else if curr.is_null().not() {
-                                contents.push(Cow::Owned(curr.to_string()));
-                            } else {
-                                errors.push(ErrorReplace::NoValue(ident))
-                            }
-                        } else {
-                            errors.push(ErrorReplace::NoValue(ident));
-                        }
-                    }
+    let commit = source.fetch(&template.0, rev)?;
+    let changes = source::changed_files(&template.0, &source.commit, &commit)?;
 
-                    value = &value[1..];
-                }
-                _ => {
-                    value = &value[1..];
-                }
-            }
+    source::checkout(&template.0, &commit, force)?;
+
+    Source {
+        url: source.url.clone(),
+        rev: rev.map(ToOwned::to_owned).or(source.rev.clone()),
+        commit,
+    }
+    .write(&template.0)?;
+
+    if changes.is_empty() {
+        info!("Template '{name}' is up to date");
+    } else {
+        info!(
+            "Updated template '{name}', changed files:\n    {}",
+            changes.join("\n    ")
+        );
+    }
+
+    Ok(())
+}
+
+/// Prints the values of every file at `paths` and the result of stashing
+/// them in order as JSON. Each key of the result records the file its value
+/// comes from and the files whose value it overrides
+fn debug_config_json(paths: &[PathBuf]) -> Result<()> {
+    let mut files = Vec::new();
+    let mut result = Values::default();
+    // Files setting each key, in the order they were stashed
+    let mut set_in = std::collections::HashMap::<String, Vec<&PathBuf>>::new();
+
+    for path in paths {
+        let values = parse_values_from_path(path, &mut String::new())?;
+
+        for key in values.value_map.keys() {
+            set_in.entry(key.clone()).or_default().push(path);
         }
 
-        if errors.is_empty() {
-            Ok(Self { contents })
-        } else {
-            Err(errors)
+        files.push(serde_json::json!({
+            "path": path,
+            "keys": values.to_json(),
+        }));
+        result = result.stash(values);
+    }
+
+    let mut keys = result.to_json();
+    for (key, entry) in &mut keys {
+        if let Some((from, overrides)) = set_in.get(key).and_then(|files| files.split_last()) {
+            entry["from"] = serde_json::json!(from);
+            entry["overrides"] = serde_json::json!(overrides);
         }
     }
-}
 
-fn confirm_remove(path: &std::path::Path) -> Result<bool> {
-    inquire::Confirm::new(&format!("Do you want to remove {}?", path.display()))
-        .with_default(false)
-        .prompt()
-        .map_err(|err| anyhow!(err))
-}
+    let errors = result
+        .verify_types()
+        .err()
+        .unwrap_or_default()
+        .iter()
+        .map(ConfigError::message)
+        .collect::<Vec<_>>();
+
+    println!(
+        "{:#}",
+        serde_json::json!({
+            "files": files,
+            "result": keys,
+            "errors": errors,
+        })
+    );
 
-fn confirm_creation(path: &std::path::Path) -> Result<bool> {
-    inquire::Confirm::new(&format!("Do you want to create {}?", path.display()))
-        .with_default(false)
-        .prompt()
-        .map_err(|err| anyhow!(err))
+    ensure!(errors.is_empty(), "Invalid types");
+    Ok(())
 }
 
-fn ask_string(key: &str) -> Result<String> {
-    inquire::prompt_text(format!("Enter a String value for field {key:?}:"))
-        .map_err(|err| anyhow!(err))
+fn confirm_remove(prompter: &mut dyn Prompter, path: &std::path::Path) -> Result<bool> {
+    prompter.confirm(&format!("Do you want to remove {}?", path.display()))
 }
 
-fn ask_any(key: &str, kind: &str, expected_type: Type) -> Result<String> {
-    inquire::Text::new(&format!("Enter {kind} value for field {key:?}:"))
-        .with_validator(move |a: &str| {
-            Ok(if a.is_empty().not() {
-                match Values::parse_value(a, "stdin") { 
-                    Ok(value) => {
-                        let val_type = Type::from_value(&value, &expected_type);
-                        if val_type.is_equivalent(&expected_type) {
-                            inquire::validator::Validation::Valid
-                        } else {
-                            Validation::Invalid(format!("Mismatching types. Expected {expected_type} but found {val_type}").into())
-                        }
-                    },
-                    Err(e) => Validation::Invalid(e.to_string().into()),
-                }
-            } else {
-                Validation::Invalid(
-                    "Empty values not allowed".to_owned().into(),
-                )
-            })
-        })
-        .prompt()
-        .map_err(|err| anyhow!(err))
-}
+fn confirm_hooks(
+    prompter: &mut dyn Prompter,
+    pre_hooks: &[String],
+    post_hooks: &[String],
+) -> Result<bool> {
+    let list = |when: &str, hooks: &[String]| {
+        if hooks.is_empty() {
+            String::new()
+        } else {
+            format!("{when}:\n    {}\n", hooks.join("\n    "))
+        }
+    };
 
-fn ask_bool(key: &str) -> Result<bool> {
-    inquire::prompt_confirmation(key).map_err(|err| anyhow!(err))
+    prompter.confirm(&format!(
+        "The template wants to run the following commands\n{}{}Do you want to run them?",
+        list("Before rendering", pre_hooks),
+        list("After rendering", post_hooks),
+    ))
 }
 
 fn main() -> ExitCode {
@@ -756,3 +1169,206 @@ fn main() -> ExitCode {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_template, diagnostic, install_from_git, parse_values_files, update_template,
+    };
+    use std::{path::Path, process::Command};
+    use temple::{config::Template, error::Location, source::Source};
+    use tera::Value;
+
+    /// Runs git inside `at`, returning its output
+    fn git(at: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(at)
+            .args([
+                "-c",
+                "user.name=temple",
+                "-c",
+                "user.email=temple@localhost",
+            ])
+            .args(args)
+            .output()
+            .expect("Failed running git");
+
+        assert!(
+            output.status.success(),
+            "git {args:?}: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        String::from_utf8_lossy(&output.stdout).trim().to_owned()
+    }
+
+    /// Commits `contents` into `file` of the clone `work` and pushes it,
+    /// returning the commit
+    fn push(work: &Path, file: &str, contents: &str) -> String {
+        std::fs::write(work.join(file), contents).expect("Failed writing file");
+        git(work, &["add", "."]);
+        git(work, &["commit", "--quiet", "--no-gpg-sign", "-m", file]);
+        git(work, &["push", "--quiet", "origin", "main"]);
+        git(work, &["rev-parse", "HEAD"])
+    }
+
+    #[test]
+    fn update_from_bare_repository() {
+        let dir = tempfile::tempdir().expect("Failed creating temp dir");
+        let remote = dir.path().join("remote.git");
+        let work = dir.path().join("work");
+        let into = dir.path().join("templates");
+        std::fs::create_dir(&into).expect("Failed creating dir");
+
+        git(
+            dir.path(),
+            &["init", "--quiet", "--bare", "-b", "main", "remote.git"],
+        );
+        git(dir.path(), &["clone", "--quiet", "remote.git", "work"]);
+        git(&work, &["checkout", "--quiet", "-b", "main"]);
+
+        push(&work, "config.tpl", "name = \"tpl\"");
+        let v1 = push(&work, "a", "1");
+        git(&work, &["tag", "v1"]);
+        git(&work, &["push", "--quiet", "origin", "v1"]);
+        push(&work, "b", "2");
+
+        let url = remote.display().to_string();
+        install_from_git(&into, &url, "tpl", Some("v1")).expect("Failed installing");
+
+        let path = into.join("tpl");
+        let template = || Template(path.clone(), Source::read(&path).expect("Valid source"));
+        let source = || template().1.expect("Installed from git");
+
+        assert_eq!(source().commit, v1);
+        assert!(!path.join("b").exists());
+
+        // Templates pinned to a revision stay on it
+        let latest = push(&work, "a", "3");
+        update_template(&template(), None, false).expect("Failed updating");
+        assert_eq!(source().commit, v1);
+        assert_eq!(source().rev.as_deref(), Some("v1"));
+
+        update_template(&template(), Some("main"), false).expect("Failed updating");
+        assert_eq!(source().commit, latest);
+        assert_eq!(source().rev.as_deref(), Some("main"));
+        assert!(path.join("b").is_file());
+
+        assert!(update_template(&template(), Some("missing"), false).is_err());
+        assert!(update_template(&template(), Some("it's \"v1\""), false).is_err());
+        assert_eq!(source().commit, latest);
+
+        // Local edits are only discarded with force
+        std::fs::write(path.join("a"), "edited").expect("Failed writing file");
+        std::fs::write(path.join("new"), "untracked").expect("Failed writing file");
+        let latest = push(&work, "b", "4");
+
+        let err = update_template(&template(), None, false).expect_err("Local edits");
+        assert!(err.to_string().contains("local edits"), "{err}");
+        assert_eq!(source().commit, git(&path, &["rev-parse", "HEAD"]));
+        assert_eq!(
+            std::fs::read_to_string(path.join("a")).expect("Failed reading"),
+            "edited"
+        );
+
+        update_template(&template(), None, true).expect("Failed updating");
+        assert_eq!(source().commit, latest);
+        assert_eq!(
+            std::fs::read_to_string(path.join("a")).expect("Failed reading"),
+            "3"
+        );
+        assert_eq!(
+            std::fs::read_to_string(path.join("b")).expect("Failed reading"),
+            "4"
+        );
+        assert!(!path.join("new").exists());
+    }
+
+    #[test]
+    fn check_fails_on_errors() {
+        let dir = tempfile::tempdir().expect("Failed creating temp dir");
+        let template = Template(dir.path().join("tpl"), None);
+        std::fs::create_dir(&template.0).expect("Failed creating dir");
+
+        std::fs::write(
+            dir.path().join("config.tpl"),
+            r#"temple_delimiters = { open: "{{", close: "}}" }"#,
+        )
+        .expect("Failed writing config");
+        std::fs::write(template.0.join("config.tpl"), "name = \"tpl\"\nunused = 1")
+            .expect("Failed writing config");
+
+        // The exit code of temple is a failure whenever the command fails
+        let check = |contents: &str| {
+            std::fs::write(template.0.join("file"), contents).expect("Failed writing file");
+            check_template(&template, dir.path(), None)
+        };
+
+        // Warnings alone do not make it fail
+        check("{{ name }}").expect("Only warnings");
+        let err = check("{{ name }} {{ nmae }}").expect_err("Undeclared key");
+        assert_eq!(
+            err.to_string(),
+            "Found 1 errors and 1 warnings in template 'tpl'"
+        );
+        check("{{ name").expect_err("Unclosed delimiter");
+    }
+
+    #[test]
+    fn diagnostic_gutter() {
+        owo_colors::set_override(false);
+
+        for (first_line, gutter) in [(1, "    |"), (998, "    |"), (1234, "     |")] {
+            let location = Location::new(Path::new("config.tpl"), "a = ~", 4..5, first_line);
+            let lines = diagnostic("Invalid token", &location)
+                .lines()
+                .map(ToOwned::to_owned)
+                .collect::<Vec<_>>();
+
+            assert_eq!(
+                lines,
+                [
+                    "Invalid token".to_owned(),
+                    format!(
+                        "{}--> config.tpl:{first_line}:5",
+                        &gutter[..gutter.len() - 2]
+                    ),
+                    gutter.to_owned(),
+                    format!("{first_line: >w$} | a = ~", w = gutter.len() - 2),
+                    format!("{gutter}     ^"),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn values_files_override_in_order() {
+        let dir = tempfile::tempdir().expect("Temp dir");
+        let files = [
+            ("a.json", r#"{ "name": "json", "count": 1, "tags": ["a"] }"#),
+            ("b.yaml", "name: yaml\ncount: 2\n"),
+            ("c.toml", "name = \"toml\"\n"),
+            ("d.tpl", "extra: String = \"tpl\""),
+        ];
+
+        let paths = files
+            .iter()
+            .map(|(name, contents)| {
+                let path = dir.path().join(name);
+                std::fs::write(&path, contents).expect("Writable");
+                path
+            })
+            .collect::<Vec<_>>();
+
+        let values = parse_values_files(&paths).expect("Valid files");
+
+        assert_eq!(values.value_map["name"], Value::from("toml"));
+        assert_eq!(values.value_map["count"], Value::from(2));
+        assert_eq!(values.value_map["tags"], Value::from(vec!["a"]));
+        assert_eq!(values.value_map["extra"], Value::from("tpl"));
+
+        std::fs::write(&paths[1], "name: [unclosed").expect("Writable");
+        assert!(parse_values_files(&paths).is_err());
+    }
+}
@@ -0,0 +1,388 @@
+use std::{collections::HashSet, path::Path};
+use walkdir::WalkDir;
+
+use crate::{
+    config::Template,
+    delimit::FileDelimiters,
+    error::{Location, RenderError},
+    expr,
+    replacer::{suggest, ContentsLexer, DelimiterType, Type as Token},
+    values::{Type, Values},
+};
+
+/// Keys temple defines on its own for every render
+pub const BUILTIN_KEYS: [&str; 3] = [
+    "temple_template_name",
+    "temple_project_name",
+    "temple_render_path",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// Place of the template the diagnostic points at, if any
+    pub location: Option<Location>,
+}
+
+impl Diagnostic {
+    fn error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            location: None,
+        }
+    }
+
+    fn warning(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+            location: None,
+        }
+    }
+
+    fn at(mut self, location: Location) -> Self {
+        self.location = Some(location);
+        self
+    }
+}
+
+impl From<RenderError> for Diagnostic {
+    fn from(err: RenderError) -> Self {
+        Diagnostic::error(err.message()).at(err.location().clone())
+    }
+}
+
+/// Lints the template at `template` against `config`, the values the template
+/// would be rendered with. Every file and templated path is lexed looking for
+/// unclosed delimiters, invalid expressions, placeholders of undeclared keys
+/// and field accesses that do not match the declared types.
+///
+/// Keys declared in `own`, the configuration of the template itself, that
+/// no file uses are reported as warnings.
+///
+/// # Errors
+///
+/// Returns an [`Err`] if the delimiters are not configured or any IO error occurs
+/// while reading the template
+pub fn check(template: &Path, config: &Values, own: &Values) -> anyhow::Result<Vec<Diagnostic>> {
+    let mut diagnostics = Vec::new();
+    let mut used = HashSet::new();
+    let file_delimiters = FileDelimiters::new(config)?;
+
+    for entry in WalkDir::new(template)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(Template::is_rendered)
+    {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy();
+
+        // Parent directories were already checked on their own
+        let lexer = ContentsLexer::new(&name, entry.path(), config)?;
+        check_contents(lexer, config, &mut used, &mut diagnostics);
+
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let Ok(contents) = std::fs::read_to_string(entry.path()) else {
+            diagnostics.push(Diagnostic::error(format!(
+                "{} is not valid UTF-8 and can not be rendered",
+                entry.path().display()
+            )));
+            continue;
+        };
+
+        let relative = entry.path().strip_prefix(template)?;
+        let header = contents.split_inclusive('\n').next().unwrap_or_default();
+
+        let (delimiters, contents, first_line) = match file_delimiters.for_file(relative, header) {
+            Ok((delimiters, true)) => (delimiters, &contents[header.len()..], 2),
+            Ok((delimiters, false)) => (delimiters, contents.as_str(), 1),
+            Err(err) => {
+                diagnostics.push(Diagnostic::error(format!(
+                    "Invalid delimiters for {}: {err}",
+                    entry.path().display()
+                )));
+                continue;
+            }
+        };
+
+        let lexer = ContentsLexer::with_delimiters(contents, entry.path(), delimiters)
+            .starting_at_line(first_line);
+        check_contents(lexer, config, &mut used, &mut diagnostics);
+    }
+
+    let mut unused = own
+        .type_map
+        .keys()
+        .filter(|k| !k.starts_with("temple_") && !used.contains(k.as_str()))
+        .collect::<Vec<_>>();
+    unused.sort();
+
+    for key in unused {
+        diagnostics.push(Diagnostic::warning(format!(
+            "Key `{key}` is declared but no file or path uses it"
+        )));
+    }
+
+    Ok(diagnostics)
+}
+
+fn check_contents(
+    mut lexer: ContentsLexer<'_>,
+    config: &Values,
+    used: &mut HashSet<String>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    // Tokens of the placeholder being lexed, if inside one, and whether any
+    // of them was invalid
+    let mut placeholder: Option<(Vec<_>, bool)> = None;
+    // Undeclared keys of the placeholder, reported once it is closed unless
+    // they are on the left of an `or`
+    let mut undeclared: Vec<(&str, Diagnostic)> = Vec::new();
+
+    while let Some(token) = lexer.next() {
+        let span = lexer.span();
+
+        if let (Ok(token), Some((tokens, _))) = (&token, placeholder.as_mut()) {
+            if !matches!(token, Token::PotentialDelim(_) | Token::Comment(_)) {
+                tokens.push((*token, span.clone()));
+            }
+        }
+
+        match token {
+            Err(err @ RenderError::InvalidToken { .. }) => {
+                diagnostics.push(err.into());
+
+                if let Some((_, invalid)) = placeholder.as_mut() {
+                    *invalid = true;
+                }
+            }
+            Err(err) => {
+                // The lexer can not move past an unclosed delimiter
+                report_undeclared(&mut undeclared, &[], diagnostics);
+                diagnostics.push(err.into());
+                break;
+            }
+            Ok(Token::PotentialDelim(DelimiterType::DelimitOpen)) => {
+                placeholder = Some((Vec::new(), false));
+            }
+            Ok(Token::PotentialDelim(DelimiterType::DelimitClose)) => {
+                let optional = match placeholder.take() {
+                    Some((tokens, false)) if !tokens.is_empty() => {
+                        match expr::parse(lexer.content, &tokens, span) {
+                            Ok(expr) => expr.optional_keys(),
+                            Err((expected, span)) => {
+                                let location = lexer.location(span);
+                                diagnostics.push(RenderError::Syntax { location, expected }.into());
+                                Vec::new()
+                            }
+                        }
+                    }
+                    _ => Vec::new(),
+                };
+
+                report_undeclared(&mut undeclared, &optional, diagnostics);
+            }
+            Ok(Token::Ident(ident)) => {
+                used.insert(ident.to_owned());
+
+                if declared_type(config, ident).is_none() {
+                    let msg = format!(
+                        "Placeholder uses the undeclared key `{ident}`{}",
+                        did_you_mean(config, ident)
+                    );
+                    undeclared.push((ident, Diagnostic::error(msg).at(lexer.location(span))));
+                }
+            }
+            Ok(Token::IdentWithField(access)) => {
+                let (ident, fields) = access.split_once('.').expect(
+                    "The REGEX does guarantee there is at least an identifier and one field",
+                );
+
+                used.insert(ident.to_owned());
+
+                let Some(mut curr) = declared_type(config, ident) else {
+                    let msg = format!(
+                        "Placeholder `{access}` uses the undeclared key `{ident}`{}",
+                        did_you_mean(config, ident)
+                    );
+                    undeclared.push((access, Diagnostic::error(msg).at(lexer.location(span))));
+                    continue;
+                };
+
+                for field in fields.split('.') {
+                    curr = match curr {
+                        Type::Object(fields) if fields.is_empty() => break,
+                        Type::Object(fields) => {
+                            if let Some(typ) = fields.get(field) {
+                                typ.clone()
+                            } else {
+                                let msg = format!(
+                                    "Placeholder `{access}` accesses the undeclared field `{field}`, expected one of: {}",
+                                    sorted_keys(&fields)
+                                );
+                                diagnostics
+                                    .push(Diagnostic::error(msg).at(lexer.location(span.clone())));
+                                break;
+                            }
+                        }
+                        Type::Array(elements) if field.parse::<i64>().is_ok() => *elements,
+                        Type::Any | Type::Unknown => break,
+                        other => {
+                            let msg = format!(
+                                "Placeholder `{access}` accesses the field `{field}` of a value of type {other}"
+                            );
+                            diagnostics
+                                .push(Diagnostic::error(msg).at(lexer.location(span.clone())));
+                            break;
+                        }
+                    };
+                }
+            }
+            Ok(_) => {}
+        }
+    }
+
+    report_undeclared(&mut undeclared, &[], diagnostics);
+}
+
+/// Reports the undeclared keys of a placeholder but the `optional` ones,
+/// told apart by the exact slice of the contents they were lexed from
+fn report_undeclared(
+    undeclared: &mut Vec<(&str, Diagnostic)>,
+    optional: &[&str],
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    diagnostics.extend(
+        undeclared
+            .drain(..)
+            .filter(|(key, _)| !optional.iter().any(|o| std::ptr::eq(*o, *key)))
+            .map(|(_, diagnostic)| diagnostic),
+    );
+}
+
+/// Returns the declared type of `key`, inferring it from its value when the
+/// declaration has no type
+fn declared_type(config: &Values, key: &str) -> Option<Type> {
+    if BUILTIN_KEYS.contains(&key) {
+        return Some(Type::String);
+    }
+
+    let decl_type = config.type_map.get(key)?;
+
+    match (decl_type, config.value_map.get(key)) {
+        (Type::Any, Some(value)) if !value.is_null() => {
+            Some(Type::from_value(value, &Type::Unknown))
+        }
+        _ => Some(decl_type.clone()),
+    }
+}
+
+fn did_you_mean(config: &Values, key: &str) -> String {
+    let declared = config
+        .type_map
+        .keys()
+        .map(String::as_str)
+        .chain(BUILTIN_KEYS);
+
+    suggest(key, declared)
+        .map(|suggestion| format!(", did you mean `{suggestion}`?"))
+        .unwrap_or_default()
+}
+
+fn sorted_keys(fields: &std::collections::HashMap<String, Type>) -> String {
+    let mut keys = fields.keys().map(String::as_str).collect::<Vec<_>>();
+    keys.sort_unstable();
+    keys.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, Severity};
+    use crate::values::Values;
+    use std::path::Path;
+
+    const CONFIG: &str = r#"temple_delimiters = { open: "{{", close: "}}" }
+name = "temple"
+author: { name: String } = { name: "ana" }
+unused: String = """#;
+
+    #[test]
+    fn diagnostics() {
+        let dir = tempfile::tempdir().expect("Failed creating temp dir");
+        let files = [
+            (
+                "fallback",
+                "{{ description or \"TODO\" }}\n{{ (nme or name) + nme }}\n",
+            ),
+            ("field", "{{ author.name }} {{ author.email }}\n"),
+            ("unclosed", "{{ name }}\n{{ name\n"),
+            ("undeclared", "{{ name }}\n\n{{ nme }}\n"),
+            ("{{ nmae }}", "{{ name }}\n"),
+        ];
+
+        for (name, contents) in files {
+            std::fs::write(dir.path().join(name), contents).expect("Failed writing file");
+        }
+
+        let config = Values::from_str(CONFIG, Path::new("config.tpl")).expect("Valid config");
+        let diagnostics = check(dir.path(), &config, &config).expect("Failed checking");
+
+        let found = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let location = diagnostic.location.as_ref().map(|location| {
+                    let file = location.path.file_name().expect("Locations point at files");
+                    (file.to_string_lossy().into_owned(), location.line)
+                });
+                (diagnostic.severity, diagnostic.message.as_str(), location)
+            })
+            .collect::<Vec<_>>();
+
+        let at = |file: &str, line| Some((file.to_owned(), line));
+        assert_eq!(
+            found,
+            [
+                // Only keys outside the left of an `or` must be declared
+                (
+                    Severity::Error,
+                    "Placeholder uses the undeclared key `nme`, did you mean `name`?",
+                    at("fallback", 2)
+                ),
+                (
+                    Severity::Error,
+                    "Placeholder `author.email` accesses the undeclared field `email`, expected one of: name",
+                    at("field", 1)
+                ),
+                (Severity::Error, "Unclosed delimiter {{", at("unclosed", 2)),
+                (
+                    Severity::Error,
+                    "Placeholder uses the undeclared key `nme`, did you mean `name`?",
+                    at("undeclared", 3)
+                ),
+                (
+                    Severity::Error,
+                    "Placeholder uses the undeclared key `nmae`, did you mean `name`?",
+                    at("{{ nmae }}", 1)
+                ),
+                // Unused keys are only warned about
+                (
+                    Severity::Warning,
+                    "Key `unused` is declared but no file or path uses it",
+                    None
+                ),
+            ]
+        );
+    }
+}
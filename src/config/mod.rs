@@ -2,7 +2,12 @@ use anyhow::{anyhow, bail, ensure, Context, Ok};
 use directories::UserDirs;
 use std::path::{Path, PathBuf};
 
-use crate::{args::Commands, info, warn};
+use crate::{
+    args::{Commands, OutputFormat},
+    info,
+    source::Source,
+    warn,
+};
 
 pub struct TempleDirs {
     user_home: PathBuf,
@@ -10,8 +15,12 @@ pub struct TempleDirs {
     local_config: Option<PathBuf>,
 }
 
+/// Files a template or a temple directory may declare its values in, in
+/// order of preference
+pub const CONFIG_FILES: [&str; 3] = ["config.tpl", "config.temple", "config.json"];
+
 #[derive(Debug, Clone)]
-pub struct Template(pub PathBuf);
+pub struct Template(pub PathBuf, pub Option<Source>);
 
 impl Template {
     #[must_use]
@@ -20,6 +29,46 @@ impl Template {
             .and_then(|name| name.to_str())
             .expect("Failed to get path file_name")
     }
+
+    /// Returns where the template was installed from, if it was installed
+    /// with `temple add`
+    #[must_use]
+    pub fn source(&self) -> Option<&Source> {
+        self.1.as_ref()
+    }
+
+    /// Returns the config file of the directory `dir`, the first one of
+    /// [`CONFIG_FILES`] that exists
+    #[must_use]
+    pub fn config_file(dir: &Path) -> Option<PathBuf> {
+        CONFIG_FILES
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.is_file())
+    }
+
+    /// Whether the entry of a template directory is rendered. Configuration
+    /// files and the `.git` directory of installed templates are not. A
+    /// `config.json` is only a configuration file when it is the one
+    /// [`Template::config_file`] picks, otherwise it is rendered as any file
+    #[must_use]
+    #[allow(clippy::case_sensitive_file_extension_comparisons)]
+    pub fn is_rendered(entry: &walkdir::DirEntry) -> bool {
+        let name = entry.file_name().to_str().unwrap_or_default();
+
+        let is_config_json = || {
+            name == "config.json"
+                && entry
+                    .path()
+                    .parent()
+                    .and_then(Template::config_file)
+                    .is_some_and(|config| config == entry.path())
+        };
+
+        !(name.ends_with(".temple")
+            || name.ends_with(".tpl")
+            || (entry.depth() == 1 && (name == ".git" || is_config_json())))
+    }
 }
 
 impl std::ops::Deref for Template {
@@ -49,6 +98,37 @@ pub enum Prefer {
 }
 
 impl Templates {
+    /// Returns the templates as a JSON array, globals first and sorted by
+    /// name. A template is shadowed when its name alone picks another
+    /// template, as local templates are with a global one of the same name
+    #[must_use]
+    pub fn to_json(&self) -> serde_json::Value {
+        let scopes = [("global", &self.global), ("local", &self.local)];
+
+        scopes
+            .into_iter()
+            .flat_map(|(scope, templates)| {
+                let mut templates = templates.iter().collect::<Vec<_>>();
+                templates.sort_by_key(|template| template.name());
+
+                templates.into_iter().map(move |template| {
+                    let name = template.name();
+                    let shadowed =
+                        scope == "local" && self.global.iter().any(|global| global.name() == name);
+
+                    serde_json::json!({
+                        "name": name,
+                        "scope": scope,
+                        "path": template.0,
+                        "config": Template::config_file(&template.0),
+                        "source": template.source(),
+                        "shadowed": shadowed,
+                    })
+                })
+            })
+            .collect()
+    }
+
     #[must_use]
     pub fn get_named(&self, name: &str, prefers: &Prefer) -> Option<&Template> {
         let local = self
@@ -174,7 +254,16 @@ impl TempleDirs {
     }
 
     pub fn display_available_templates(&self, config: &Commands) -> anyhow::Result<()> {
-        if let Commands::List { short, path, .. } = config {
+        if let Commands::List {
+            format: OutputFormat::Json,
+            ..
+        } = config
+        {
+            let templates = self.get_available_templates()?;
+            println!("{:#}", templates.to_json());
+
+            Ok(())
+        } else if let Commands::List { short, path, .. } = config {
             let long = !short;
             let globals = Self::get_templates_in_dir(&self.global_config)?;
             let locals = if let Some(l) = self
@@ -218,11 +307,16 @@ impl TempleDirs {
                         "{}",
                         iter.iter()
                             .map(|a| format!(
-                                "{dotchr}{tename}{tepath}{spacer}",
+                                "{dotchr}{tename}{tesource}{tepath}{spacer}",
                                 dotchr = if long { "    " } else { "" },
                                 tename = (long || i == 0)
                                     .then_some(a.name().to_string())
                                     .unwrap_or_else(|| format!("local:{}", a.name())),
+                                tesource = a
+                                    .source()
+                                    .filter(|_| long)
+                                    .map(|source| format!(" (from {source})"))
+                                    .unwrap_or_default(),
                                 tepath = path
                                     .then_some(format!(
                                         "\t'{}'",
@@ -361,13 +455,8 @@ impl TempleDirs {
 
                 crate::trace!("Looking at {}", path.display());
 
-                if path.exists() && path.is_dir() {
-                    for opt in ["config.tpl", "config.temple"] {
-                        let config = path.join(opt);
-                        if config.exists() && config.is_file() {
-                            return Ok(Some(path));
-                        }
-                    }
+                if path.is_dir() && Template::config_file(&path).is_some() {
+                    return Ok(Some(path));
                 }
             }
             curr = parent.parent();
@@ -381,6 +470,8 @@ impl TempleDirs {
     /// - The path is a directory
     /// - It contains a `.temple` file
     ///
+    /// Templates installed from a source carry its metadata, see [`Source::read`].
+    ///
     /// # Errors
     ///
     /// This function will return an error if any IO error happens while getting path
@@ -394,13 +485,17 @@ impl TempleDirs {
 
         for entry in path.read_dir()? {
             let entry = entry?;
-            let root = entry.path().join("config.tpl");
-            let root2 = entry.path().join("config.temple");
 
-            if entry.file_type()?.is_dir()
-                && ((root.exists() && root.is_file()) || (root2.exists() && root2.is_file()))
-            {
-                res.push(Template(entry.path()));
+            if entry.file_type()?.is_dir() && Template::config_file(&entry.path()).is_some() {
+                let source = Source::read(&entry.path()).unwrap_or_else(|err| {
+                    warn!(
+                        "Ignoring invalid source metadata of {}: {err}",
+                        entry.path().display()
+                    );
+                    None
+                });
+
+                res.push(Template(entry.path(), source));
             }
         }
 
@@ -429,3 +524,35 @@ impl TempleDirs {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Template;
+    use walkdir::WalkDir;
+
+    fn rendered(template: &std::path::Path) -> Vec<String> {
+        WalkDir::new(template)
+            .min_depth(1)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(Template::is_rendered)
+            .map(|entry| {
+                let entry = entry.expect("Readable entry");
+                entry.file_name().to_string_lossy().into_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn config_json_is_rendered_unless_picked() {
+        let dir = tempfile::tempdir().expect("Temp dir");
+        let template = dir.path();
+
+        std::fs::write(template.join("config.json"), "{}").expect("Writable");
+        std::fs::write(template.join("main.rs"), "").expect("Writable");
+        assert_eq!(rendered(template), ["main.rs"]);
+
+        std::fs::write(template.join("config.tpl"), "").expect("Writable");
+        assert_eq!(rendered(template), ["config.json", "main.rs"]);
+    }
+}
@@ -1,5 +1,23 @@
 use anyhow::{anyhow, ensure};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::path::Path;
 
+use crate::{replacer::TRIM_MARKER, values::Values};
+
+/// Key of the delimiters used by every file without an override
+pub const DELIMITERS: &str = "temple_delimiters";
+
+/// Key of the table of delimiters for the files matching each glob
+pub const DELIMITERS_FOR: &str = "temple_delimiters_for";
+
+/// Marker of the first line header that sets the delimiters of a file
+pub const HEADER: &str = "temple_delimiters:";
+
+/// Openings of the comments a delimiters header can be written in
+pub const HEADER_COMMENTS: [&str; 10] =
+    ["#", "//", "/*", "--", "<!--", ";", "%", "{#", "{{/*", "{{!"];
+
+#[derive(Debug, Clone, Copy)]
 pub struct Delimiters<'a>(pub Delimiter<'a>, pub Delimiter<'a>);
 
 impl Delimiters<'_> {
@@ -38,11 +56,68 @@ impl Delimiters<'_> {
     pub fn find_end(&self, contents: &str, from: usize) -> Option<usize> {
         self.1.find_in(contents, from)
     }
+
+    /// Returns the start, the end past the close delimiter and the trimmed
+    /// contents, without trim markers, of the first placeholder found from `from`
+    #[must_use]
+    pub fn find_placeholder<'s>(
+        &self,
+        contents: &'s str,
+        from: usize,
+    ) -> Option<(usize, usize, &'s str)> {
+        let start = self.find_start(contents, from)? + from;
+        let inner = start + self.0.len();
+        let end = self.find_end(contents, inner)? + inner;
+
+        let tag = &contents[inner..end];
+        let tag = tag.strip_prefix(TRIM_MARKER).unwrap_or(tag);
+        let tag = tag.strip_suffix(TRIM_MARKER).unwrap_or(tag);
+
+        Some((start, end + self.1.len(), tag.trim()))
+    }
 }
 
 impl<'a> Delimiters<'a> {
-    // TODO: Remove dead_code
-    #[allow(dead_code)]
+    /// Returns the delimiters set with `temple_delimiters`
+    pub fn from_config(config: &'a Values) -> anyhow::Result<Self> {
+        config
+            .value_map
+            .get(DELIMITERS)
+            .ok_or(anyhow!(
+                "Delimiters must be set with the identifier '{DELIMITERS}'"
+            ))?
+            .try_into()
+    }
+
+    /// Returns the delimiters set by a header in `line`, like
+    /// `# temple_delimiters: << >>`. The header is [`HEADER`] inside a comment
+    /// opened at the start of the line with any of [`HEADER_COMMENTS`], so
+    /// that it fits in a file of most languages. Lines that are not a valid
+    /// header are contents like any other
+    #[must_use]
+    pub fn from_header(line: &'a str) -> Option<Self> {
+        let comment = HEADER_COMMENTS
+            .iter()
+            .find_map(|opening| line.strip_prefix(opening))?;
+        let header = comment.trim_start().strip_prefix(HEADER)?;
+        let mut parts = header.split_whitespace();
+
+        let delimiters = match (parts.next(), parts.next()) {
+            (Some(open), Some(close)) if open != close => Self::new(open, close).ok(),
+            _ => None,
+        };
+
+        if delimiters.is_none() {
+            crate::trace!(
+                "Ignoring invalid delimiters header {:?}, expected `{} <open> <close>`",
+                line.trim_end(),
+                HEADER
+            );
+        }
+
+        delimiters
+    }
+
     fn new(open: &'a str, close: &'a str) -> anyhow::Result<Self> {
         ensure!(
             open.len() >= 2,
@@ -65,6 +140,82 @@ impl<'a> Delimiters<'a> {
     }
 }
 
+/// Picks the delimiters of each file of a template, compiling the globs of
+/// `temple_delimiters_for` once for all of them
+#[derive(Debug, Clone)]
+pub struct FileDelimiters<'a> {
+    config: &'a Values,
+    globs: GlobSet,
+    /// Globs of `globs`, in its order, with their delimiters
+    table: Vec<(&'a str, &'a tera::Value)>,
+}
+
+impl<'a> FileDelimiters<'a> {
+    /// Compiles the globs of `temple_delimiters_for` in `config`. Delimiters
+    /// are only checked once a file uses them
+    pub fn new(config: &'a Values) -> anyhow::Result<Self> {
+        let mut globs = GlobSetBuilder::new();
+        let mut table = Vec::new();
+
+        if let Some(value) = config.value_map.get(DELIMITERS_FOR) {
+            let value = value.as_object().ok_or(anyhow!(
+                "Invalid value for '{DELIMITERS_FOR}', expected an object of globs and delimiters"
+            ))?;
+
+            for (glob, delimiters) in value {
+                globs.add(Glob::new(glob).map_err(|err| {
+                    anyhow!("Invalid glob '{glob}' in '{DELIMITERS_FOR}': {err}")
+                })?);
+                table.push((glob.as_str(), delimiters));
+            }
+        }
+
+        Ok(Self {
+            config,
+            globs: globs
+                .build()
+                .map_err(|err| anyhow!("Invalid globs in '{DELIMITERS_FOR}': {err}"))?,
+            table,
+        })
+    }
+
+    /// Returns the delimiters for the file at `path`, relative to the
+    /// template. These are the ones of the longest glob in
+    /// `temple_delimiters_for` matching the path, or `temple_delimiters` if
+    /// none does
+    pub fn for_path(&self, path: &Path) -> anyhow::Result<Delimiters<'a>> {
+        let mut matched: Option<(&str, &tera::Value)> = None;
+
+        for i in self.globs.matches(path) {
+            let (glob, delimiters) = self.table[i];
+
+            if matched.is_none_or(|(longest, _)| glob.len() > longest.len()) {
+                matched = Some((glob, delimiters));
+            }
+        }
+
+        match matched {
+            Some((glob, delimiters)) => delimiters.try_into().map_err(|err| {
+                anyhow!("Invalid delimiters for '{glob}' in '{DELIMITERS_FOR}': {err}")
+            }),
+            None => Delimiters::from_config(self.config),
+        }
+    }
+
+    /// Returns the delimiters for the file at `path`, relative to the
+    /// template, whose first line is `line`, and whether the line is a
+    /// header to leave out of the contents
+    pub fn for_file<'l>(&self, path: &Path, line: &'l str) -> anyhow::Result<(Delimiters<'l>, bool)>
+    where
+        'a: 'l,
+    {
+        match Delimiters::from_header(line) {
+            Some(delimiters) => Ok((delimiters, true)),
+            None => Ok((self.for_path(path)?, false)),
+        }
+    }
+}
+
 impl<'a> TryFrom<&'a tera::Value> for Delimiters<'a> {
     type Error = anyhow::Error;
 
@@ -117,3 +268,77 @@ impl std::ops::Deref for Delimiter<'_> {
         self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Delimiters, FileDelimiters};
+    use crate::values::Values;
+    use std::path::Path;
+
+    const CONFIG: &str = r#"temple_delimiters = { open: "{{", close: "}}" }
+temple_delimiters_for = {
+    "*.yml": { open: "<<", close: ">>" },
+    "ci/*.yml": { open: "<%", close: "%>" },
+}"#;
+
+    #[test]
+    fn header() {
+        let open_close = |line| Delimiters::from_header(line).map(|d| (d.0 .0, d.1 .0));
+
+        assert_eq!(
+            open_close("# temple_delimiters: << >>\n"),
+            Some(("<<", ">>"))
+        );
+        assert_eq!(open_close("//temple_delimiters: [[ ]]"), Some(("[[", "]]")));
+        assert_eq!(
+            open_close("{{/* temple_delimiters: [[ ]] */}}"),
+            Some(("[[", "]]"))
+        );
+        assert_eq!(
+            open_close("<!-- temple_delimiters: <% %> -->"),
+            Some(("<%", "%>"))
+        );
+
+        // Not headers, their line is contents like any other
+        assert_eq!(open_close("temple_delimiters: { open: String }"), None);
+        assert_eq!(open_close("  # temple_delimiters: << >>"), None);
+        assert_eq!(open_close("x = 1 # temple_delimiters: << >>"), None);
+        assert_eq!(open_close("# temple_delimiters: <<"), None);
+        assert_eq!(open_close("# temple_delimiters: << <<"), None);
+        assert_eq!(open_close("# temple_delimiters: < >"), None);
+    }
+
+    #[test]
+    fn longest_glob() {
+        let config = Values::from_str(CONFIG, Path::new("config.tpl")).expect("Valid config");
+        let delimiters = FileDelimiters::new(&config).expect("Valid globs");
+        let open = |path| {
+            delimiters
+                .for_path(Path::new(path))
+                .expect("Valid delimiters")
+                .0
+                 .0
+        };
+
+        assert_eq!(open("main.rs"), "{{");
+        assert_eq!(open("docker.yml"), "<<");
+        assert_eq!(open("ci/build.yml"), "<%");
+
+        let (file, is_header) = delimiters
+            .for_file(Path::new("ci/build.yml"), "# temple_delimiters: [[ ]]\n")
+            .expect("Valid delimiters");
+        assert_eq!((file.delimiters(), is_header), (("[[", "]]"), true));
+
+        let (file, is_header) = delimiters
+            .for_file(
+                Path::new("config.tpl"),
+                "temple_delimiters: { open: String }\n",
+            )
+            .expect("Valid delimiters");
+        assert_eq!((file.delimiters(), is_header), (("{{", "}}"), false));
+
+        let config = Values::from_str(r#"temple_delimiters_for = { "[": {} }"#, Path::new("c"))
+            .expect("Valid config");
+        assert!(FileDelimiters::new(&config).is_err());
+    }
+}
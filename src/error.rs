@@ -0,0 +1,254 @@
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    expr,
+    values::{Format, Type},
+};
+
+/// Place of a file an error points at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    pub path: PathBuf,
+    /// Line, starting at 1
+    pub line: usize,
+    /// Columns of the line, counted in chars and starting at 1
+    pub columns: Range<usize>,
+    /// Contents of the line, to show the error in context
+    pub source: String,
+}
+
+impl Location {
+    /// Returns the location of the byte `span` of `contents`, which start at
+    /// the line `first_line` of the file at `path`
+    #[must_use]
+    pub fn new(path: &Path, contents: &str, span: Range<usize>, first_line: usize) -> Self {
+        let start = span.start.min(contents.len());
+        let end = span.end.clamp(start, contents.len());
+
+        let before = &contents[..start];
+        let line_start = before.rfind('\n').map_or(0, |n| n + 1);
+        let line_end = contents[start..]
+            .find('\n')
+            .map_or(contents.len(), |n| start + n);
+
+        let col = before[line_start..].chars().count() + 1;
+        let len = contents[start..end].chars().count();
+
+        Self {
+            path: path.to_path_buf(),
+            line: first_line + before.matches('\n').count(),
+            columns: col..col + len,
+            source: contents[line_start..line_end]
+                .trim_end_matches('\r')
+                .to_owned(),
+        }
+    }
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.path.display(),
+            self.line,
+            self.columns.start
+        )
+    }
+}
+
+/// Error in a configuration or in a value parsed with the config syntax
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// Invalid syntax, with what was expected and the token found instead
+    Syntax {
+        location: Location,
+        expected: String,
+        found: Option<String>,
+    },
+    /// Invalid document in a data format, like a JSON file of values
+    Format {
+        format: Format,
+        location: Option<Location>,
+        message: String,
+    },
+    /// The value of `key` does not match its declared type
+    TypeMismatch {
+        key: String,
+        value: tera::Value,
+        declared: Box<Type>,
+        found: Box<Type>,
+    },
+    /// The value of `key` can not be written in the config syntax
+    Unwritable { key: String, message: String },
+    /// `key` is defined more than once, the last definition is kept.
+    /// Reported as a warning
+    Duplicate { location: Location, key: String },
+    /// `key` has neither a value nor a type. Reported as a warning
+    Untyped { location: Location, key: String },
+}
+
+impl ConfigError {
+    #[must_use]
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            ConfigError::Syntax { location, .. }
+            | ConfigError::Duplicate { location, .. }
+            | ConfigError::Untyped { location, .. } => Some(location),
+            ConfigError::Format { location, .. } => location.as_ref(),
+            ConfigError::TypeMismatch { .. } | ConfigError::Unwritable { .. } => None,
+        }
+    }
+
+    /// Returns the description of the error, without its location
+    #[must_use]
+    pub fn message(&self) -> String {
+        match self {
+            ConfigError::Syntax {
+                expected, found, ..
+            } => match found {
+                Some(found) => format!("Invalid syntax, expected {expected} but found {found}"),
+                None => format!("Invalid syntax, expected {expected}"),
+            },
+            ConfigError::Format {
+                format, message, ..
+            } => format!("Invalid {format}, {message}"),
+            ConfigError::TypeMismatch {
+                key,
+                value,
+                declared,
+                found,
+            } => format!(
+                "The value of '{key}' does not match with the declared type\n    Value: {v}\n    Decl type: {declared}\n    Real type: {found}",
+                v = format!("{value:#}").replace('\n', "\n    ")
+            ),
+            ConfigError::Unwritable { key, message } => {
+                format!("The value of '{key}' can not be written in the config syntax, {message}")
+            }
+            ConfigError::Duplicate { key, .. } => {
+                format!("Ident `{key}` is already defined, overriding")
+            }
+            ConfigError::Untyped { key, .. } => {
+                format!("We advise to state a data type for the empty value of `{key}`")
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.location() {
+            Some(location) => write!(f, "{location}: {}", self.message()),
+            None => f.write_str(&self.message()),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Error in the contents or the path of a file being rendered
+#[derive(Debug, Clone, PartialEq)]
+pub enum RenderError {
+    /// An open delimiter without its close delimiter
+    UnclosedDelimiter {
+        location: Location,
+        delimiter: String,
+    },
+    /// A comment or a raw block without its end, with the end expected
+    UnclosedBlock {
+        location: Location,
+        expected: String,
+    },
+    /// Text inside delimiters that is not a valid token
+    InvalidToken { location: Location, token: String },
+    /// A placeholder that is not a valid expression, like `{{ (a }}`
+    Syntax {
+        location: Location,
+        expected: String,
+    },
+    /// A placeholder using a key, field or index without a value
+    MissingValue {
+        location: Location,
+        error: Box<expr::Error>,
+    },
+    /// A placeholder applying an operation to values of the wrong type, or
+    /// evaluating to a value that can't be output
+    TypeMismatch {
+        location: Location,
+        error: Box<expr::Error>,
+    },
+    /// A placeholder whose arithmetic can't be computed, like a division by zero
+    Arithmetic {
+        location: Location,
+        error: Box<expr::Error>,
+    },
+}
+
+impl RenderError {
+    /// Returns the error found evaluating the placeholder at `location`
+    #[must_use]
+    pub fn evaluating(location: Location, error: expr::Error) -> Self {
+        use expr::Error as E;
+
+        let error = Box::new(error);
+        match *error {
+            E::NoValue(..)
+            | E::NullValue(_)
+            | E::NoField(..)
+            | E::NoKey(..)
+            | E::OutOfRange(..) => RenderError::MissingValue { location, error },
+            E::DivisionByZero | E::Overflow(_) => RenderError::Arithmetic { location, error },
+            E::ExpectedValue(_)
+            | E::UnexpectedObject(_)
+            | E::UnexpectedField(..)
+            | E::Unprintable(_)
+            | E::Operands(..)
+            | E::Negate(_)
+            | E::Index(..) => RenderError::TypeMismatch { location, error },
+        }
+    }
+
+    #[must_use]
+    pub fn location(&self) -> &Location {
+        match self {
+            RenderError::UnclosedDelimiter { location, .. }
+            | RenderError::UnclosedBlock { location, .. }
+            | RenderError::InvalidToken { location, .. }
+            | RenderError::Syntax { location, .. }
+            | RenderError::MissingValue { location, .. }
+            | RenderError::TypeMismatch { location, .. }
+            | RenderError::Arithmetic { location, .. } => location,
+        }
+    }
+
+    /// Returns the description of the error, without its location
+    #[must_use]
+    pub fn message(&self) -> String {
+        match self {
+            RenderError::UnclosedDelimiter { delimiter, .. } => {
+                format!("Unclosed delimiter {delimiter}")
+            }
+            RenderError::UnclosedBlock { expected, .. } => {
+                format!("Unclosed block, expected {expected}")
+            }
+            RenderError::InvalidToken { token, .. } => {
+                format!("Invalid token `{token}` inside delimiters")
+            }
+            RenderError::Syntax { expected, .. } => format!("Expected {expected}"),
+            RenderError::MissingValue { error, .. }
+            | RenderError::TypeMismatch { error, .. }
+            | RenderError::Arithmetic { error, .. } => error.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for RenderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.location(), self.message())
+    }
+}
+
+impl std::error::Error for RenderError {}
@@ -0,0 +1,738 @@
+use logos::Span;
+use std::{borrow::Cow, cmp::Ordering};
+use tera::{Number, Value};
+
+use crate::{
+    replacer::{suggest, Type},
+    values::Values,
+};
+
+/// Expression inside a placeholder, like `description or "TODO"`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr<'i> {
+    Literal(Value),
+    /// A key, followed by the fields accessed if any, like `author.name`
+    Key(&'i str),
+    Not(Box<Expr<'i>>),
+    Neg(Box<Expr<'i>>),
+    Binary(Op, Box<Expr<'i>>, Box<Expr<'i>>),
+    /// An index or a key looked up in brackets, like `authors[-1]` or `config[key]`
+    Index(Box<Expr<'i>>, Box<Expr<'i>>),
+    /// A field accessed after an index, like `.email` in `authors[0].email`
+    Field(Box<Expr<'i>>, &'i str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl std::fmt::Display for Op {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Op::Or => "or",
+            Op::And => "and",
+            Op::Eq => "==",
+            Op::Ne => "!=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Add => "+",
+            Op::Sub => "-",
+            Op::Mul => "*",
+            Op::Div => "/",
+            Op::Rem => "%",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// Undeclared key, with the closest declared key
+    NoValue(String, Option<String>),
+    NullValue(String),
+    /// Undeclared field of an access, with the closest existing field
+    NoField(String, String, Option<String>),
+    ExpectedValue(String),
+    UnexpectedObject(String),
+    UnexpectedField(String, String),
+    /// Key missing from an object, with the closest existing key
+    NoKey(String, Option<String>),
+    OutOfRange(i64, usize),
+    Index(&'static str, &'static str),
+    /// Expression evaluating to an object or to null, which can't be output
+    Unprintable(&'static str),
+    Operands(Op, &'static str, &'static str),
+    Negate(&'static str),
+    DivisionByZero,
+    Overflow(Op),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NoValue(ident, suggestion) => {
+                write!(f, "Placeholder uses the undeclared key `{ident}`")?;
+                if let Some(suggestion) = suggestion {
+                    write!(f, ", did you mean `{suggestion}`?")?;
+                }
+                Ok(())
+            }
+            Error::NullValue(ident) => {
+                write!(f, "Placeholder uses the key `{ident}` which has no value")
+            }
+            Error::NoField(access, field, suggestion) => {
+                write!(
+                    f,
+                    "Placeholder `{access}` accesses the undeclared field `{field}`"
+                )?;
+                if let Some(suggestion) = suggestion {
+                    write!(f, ", did you mean `{suggestion}`?")?;
+                }
+                Ok(())
+            }
+            Error::ExpectedValue(ident) => write!(
+                f,
+                "Placeholder uses the key `{ident}` which is an object, access one of its fields instead"
+            ),
+            Error::UnexpectedObject(access) => write!(
+                f,
+                "Placeholder `{access}` is an object, access one of its fields instead"
+            ),
+            Error::UnexpectedField(access, field) => write!(
+                f,
+                "Placeholder `{access}` accesses the field `{field}` of a value that is not an object"
+            ),
+            Error::NoKey(key, suggestion) => {
+                write!(f, "Placeholder looks up the key `{key}` which the object does not have")?;
+                if let Some(suggestion) = suggestion {
+                    write!(f, ", did you mean `{suggestion}`?")?;
+                }
+                Ok(())
+            }
+            Error::OutOfRange(index, len) => write!(
+                f,
+                "Placeholder accesses the index {index} of an array of length {len}"
+            ),
+            Error::Index(typ, index) => write!(
+                f,
+                "Can not index a value of type {typ} with a value of type {index}"
+            ),
+            Error::Unprintable(typ) => {
+                write!(f, "Placeholder evaluates to a value of type {typ} which can not be output")
+            }
+            Error::Operands(op, lhs, rhs) => {
+                write!(f, "Can not apply `{op}` to values of type {lhs} and {rhs}")
+            }
+            Error::Negate(typ) => write!(f, "Can not negate a value of type {typ}"),
+            Error::DivisionByZero => write!(f, "Division by zero"),
+            Error::Overflow(op) => write!(f, "Overflow while applying `{op}`"),
+        }
+    }
+}
+
+/// Parses the tokens found inside a pair of delimiters, `close` being the
+/// span of the close delimiter and `source` the contents both spans point
+/// into. On failure returns what was expected and the span of the
+/// unexpected token
+pub fn parse<'i>(
+    source: &str,
+    tokens: &[(Type<'i>, Span)],
+    close: Span,
+) -> Result<Expr<'i>, (String, Span)> {
+    let mut parser = Parser {
+        source,
+        tokens,
+        pos: 0,
+        close,
+    };
+
+    let expr = parser.or()?;
+
+    match parser.tokens.get(parser.pos) {
+        Some((_, span)) => Err((
+            "an operator or the end of the placeholder".to_owned(),
+            span.clone(),
+        )),
+        None => Ok(expr),
+    }
+}
+
+/// Recursive descent parser, from the lowest to the highest precedence:
+/// `or`, `and`, `not`, comparisons, `+ -`, `* / %`, unary `-` and indexes
+struct Parser<'t, 'i> {
+    source: &'t str,
+    tokens: &'t [(Type<'i>, Span)],
+    pos: usize,
+    close: Span,
+}
+
+impl<'i> Parser<'_, 'i> {
+    fn peek(&self) -> Option<Type<'i>> {
+        self.tokens.get(self.pos).map(|(token, _)| *token)
+    }
+
+    fn span(&self) -> Span {
+        self.tokens
+            .get(self.pos)
+            .map_or_else(|| self.close.clone(), |(_, span)| span.clone())
+    }
+
+    fn binary(
+        &mut self,
+        operand: fn(&mut Self) -> Result<Expr<'i>, (String, Span)>,
+        op: fn(Type<'i>) -> Option<Op>,
+    ) -> Result<Expr<'i>, (String, Span)> {
+        let mut lhs = operand(self)?;
+
+        while let Some(op) = self.peek().and_then(op) {
+            self.pos += 1;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(operand(self)?));
+        }
+
+        Ok(lhs)
+    }
+
+    fn or(&mut self) -> Result<Expr<'i>, (String, Span)> {
+        self.binary(Self::and, |token| {
+            matches!(token, Type::KwOr).then_some(Op::Or)
+        })
+    }
+
+    fn and(&mut self) -> Result<Expr<'i>, (String, Span)> {
+        self.binary(Self::not, |token| {
+            matches!(token, Type::KwAnd).then_some(Op::And)
+        })
+    }
+
+    fn not(&mut self) -> Result<Expr<'i>, (String, Span)> {
+        if matches!(self.peek(), Some(Type::KwNot)) {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr<'i>, (String, Span)> {
+        self.binary(Self::sum, |token| match token {
+            Type::Eq => Some(Op::Eq),
+            Type::Ne => Some(Op::Ne),
+            Type::Lt => Some(Op::Lt),
+            Type::Le => Some(Op::Le),
+            Type::Gt => Some(Op::Gt),
+            Type::Ge => Some(Op::Ge),
+            _ => None,
+        })
+    }
+
+    fn sum(&mut self) -> Result<Expr<'i>, (String, Span)> {
+        let mut lhs = self.product()?;
+
+        loop {
+            let op = match self.peek() {
+                Some(Type::Plus) => Op::Add,
+                Some(Type::Minus) => Op::Sub,
+                // `a -1` is lexed as a key followed by a signed number, which
+                // is left as the start of the right operand
+                Some(Type::SNumber(_) | Type::FNumber(_))
+                    if self.source[self.span()].starts_with(['+', '-']) =>
+                {
+                    lhs = Expr::Binary(Op::Add, Box::new(lhs), Box::new(self.product()?));
+                    continue;
+                }
+                _ => return Ok(lhs),
+            };
+
+            self.pos += 1;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr<'i>, (String, Span)> {
+        self.binary(Self::unary, |token| match token {
+            Type::Star => Some(Op::Mul),
+            Type::Slash => Some(Op::Div),
+            Type::Percent => Some(Op::Rem),
+            _ => None,
+        })
+    }
+
+    fn unary(&mut self) -> Result<Expr<'i>, (String, Span)> {
+        if matches!(self.peek(), Some(Type::Minus)) {
+            self.pos += 1;
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+
+        self.postfix()
+    }
+
+    fn postfix(&mut self) -> Result<Expr<'i>, (String, Span)> {
+        let mut expr = self.primary()?;
+
+        loop {
+            match self.peek() {
+                Some(Type::SqOpen) => {
+                    self.pos += 1;
+                    let index = self.or()?;
+
+                    if !matches!(self.peek(), Some(Type::SqClose)) {
+                        return Err(("`]`".to_owned(), self.span()));
+                    }
+                    self.pos += 1;
+
+                    expr = Expr::Index(Box::new(expr), Box::new(index));
+                }
+                Some(Type::Field(field)) => {
+                    self.pos += 1;
+                    expr = Expr::Field(Box::new(expr), field);
+                }
+                _ => return Ok(expr),
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr<'i>, (String, Span)> {
+        let span = self.span();
+        let Some(token) = self.peek() else {
+            return Err(("a value".to_owned(), span));
+        };
+        self.pos += 1;
+
+        Ok(match token {
+            Type::UNumber(n) => Expr::Literal(n.into()),
+            Type::SNumber(n) => Expr::Literal(n.into()),
+            Type::FNumber(n) => Expr::Literal(n.into()),
+            Type::String(s) => Expr::Literal(s.into()),
+            Type::Bool(b) => Expr::Literal(b.into()),
+            Type::Ident(access) | Type::IdentWithField(access) => Expr::Key(access),
+            Type::ParOpen => {
+                let expr = self.or()?;
+
+                if !matches!(self.peek(), Some(Type::ParClose)) {
+                    return Err(("`)`".to_owned(), self.span()));
+                }
+                self.pos += 1;
+
+                expr
+            }
+            _ => return Err(("a value".to_owned(), span)),
+        })
+    }
+}
+
+impl<'a> Expr<'a> {
+    /// Evaluates the expression and returns the text it is replaced with
+    pub fn render(&self, values: &'a Values) -> Result<Cow<'a, str>, Error> {
+        let value = self.eval(values)?;
+
+        match (self, value) {
+            (Expr::Key(access), Cow::Borrowed(Value::Object(_))) => {
+                Err(match access.split_once('.') {
+                    Some(_) => Error::UnexpectedObject(access.to_string()),
+                    None => Error::ExpectedValue(access.to_string()),
+                })
+            }
+            (Expr::Key(access), Cow::Borrowed(Value::Null)) => Err(Error::NullValue(
+                access
+                    .split_once('.')
+                    .map_or(*access, |(ident, _)| ident)
+                    .to_string(),
+            )),
+            (_, value @ Cow::Borrowed(Value::Object(_) | Value::Null)) => {
+                Err(Error::Unprintable(type_name(&value)))
+            }
+            (_, Cow::Owned(value @ (Value::Object(_) | Value::Null))) => {
+                Err(Error::Unprintable(type_name(&value)))
+            }
+            (_, Cow::Borrowed(Value::String(s))) => Ok(Cow::Borrowed(s)),
+            (_, Cow::Owned(Value::String(s))) => Ok(Cow::Owned(s)),
+            (_, value) => Ok(Cow::Owned(value.to_string())),
+        }
+    }
+
+    /// Evaluates the expression against `values`
+    pub fn eval(&self, values: &'a Values) -> Result<Cow<'a, Value>, Error> {
+        Ok(match self {
+            Expr::Literal(value) => Cow::Owned(value.clone()),
+            Expr::Key(access) => Cow::Borrowed(lookup(access, values)?),
+            Expr::Not(expr) => Cow::Owned((!is_truthy(&*expr.eval(values)?)).into()),
+            Expr::Neg(expr) => {
+                let value = expr.eval(values)?;
+                let negated = match value.as_ref() {
+                    Value::Number(n) => match n.as_i64() {
+                        Some(n) => n.checked_neg().map(Value::from),
+                        None => n.as_f64().map(|n| (-n).into()),
+                    },
+                    _ => return Err(Error::Negate(type_name(&value))),
+                };
+
+                Cow::Owned(negated.ok_or(Error::Overflow(Op::Sub))?)
+            }
+            // Keys without a value on the left fall back to the right too
+            Expr::Binary(Op::Or, lhs, rhs) => match lhs.eval(values) {
+                Ok(lhs) if is_truthy(&lhs) => lhs,
+                Ok(_) | Err(Error::NoValue(..) | Error::NullValue(_)) => rhs.eval(values)?,
+                Err(err) => return Err(err),
+            },
+            Expr::Binary(Op::And, lhs, rhs) => {
+                let lhs = lhs.eval(values)?;
+                if is_truthy(&lhs) {
+                    rhs.eval(values)?
+                } else {
+                    lhs
+                }
+            }
+            Expr::Index(base, index) => {
+                let index = index.eval(values)?;
+                entry_of(base.eval(values)?, &index)?
+            }
+            Expr::Field(base, field) => entry_of(base.eval(values)?, &Value::from(*field))?,
+            Expr::Binary(op, lhs, rhs) => {
+                Cow::Owned(apply(*op, &*lhs.eval(values)?, &*rhs.eval(values)?)?)
+            }
+        })
+    }
+
+    /// Returns the keys used on the left of an `or`, which fall back to its
+    /// right side when they are undeclared
+    #[must_use]
+    pub fn optional_keys(&self) -> Vec<&'a str> {
+        fn walk<'a>(expr: &Expr<'a>, optional: bool, keys: &mut Vec<&'a str>) {
+            match expr {
+                Expr::Literal(_) => {}
+                Expr::Key(access) => {
+                    if optional {
+                        keys.push(access);
+                    }
+                }
+                Expr::Not(expr) | Expr::Neg(expr) | Expr::Field(expr, _) => {
+                    walk(expr, optional, keys);
+                }
+                Expr::Binary(Op::Or, lhs, rhs) => {
+                    walk(lhs, true, keys);
+                    walk(rhs, optional, keys);
+                }
+                Expr::Binary(_, lhs, rhs) | Expr::Index(lhs, rhs) => {
+                    walk(lhs, optional, keys);
+                    walk(rhs, optional, keys);
+                }
+            }
+        }
+
+        let mut keys = Vec::new();
+        walk(self, false, &mut keys);
+        keys
+    }
+}
+
+/// Returns the value of `access`, a key followed by the fields accessed
+fn lookup<'a>(access: &'a str, values: &'a Values) -> Result<&'a Value, Error> {
+    let (ident, fields) = match access.split_once('.') {
+        Some((ident, fields)) => (ident, Some(fields)),
+        None => (access, None),
+    };
+
+    let Some(mut curr) = values.value_map.get(ident) else {
+        let suggestion = suggest(ident, values.value_map.keys().map(String::as_str));
+        return Err(Error::NoValue(
+            ident.to_string(),
+            suggestion.map(str::to_owned),
+        ));
+    };
+
+    for field in fields.into_iter().flat_map(|fields| fields.split('.')) {
+        curr = match curr {
+            Value::Object(object) => object.get(field).ok_or_else(|| {
+                Error::NoField(
+                    access.to_string(),
+                    field.to_string(),
+                    suggest(field, object.keys().map(String::as_str)).map(str::to_owned),
+                )
+            })?,
+            Value::Array(_) => match field.parse::<i64>() {
+                Ok(index) => entry(curr, &index.into())?,
+                Err(_) => {
+                    return Err(Error::UnexpectedField(
+                        access.to_string(),
+                        field.to_string(),
+                    ))
+                }
+            },
+            _ => {
+                return Err(Error::UnexpectedField(
+                    access.to_string(),
+                    field.to_string(),
+                ))
+            }
+        };
+    }
+
+    Ok(curr)
+}
+
+/// Returns the entry of `base` at `index`, borrowed if `base` is
+fn entry_of<'a>(base: Cow<'a, Value>, index: &Value) -> Result<Cow<'a, Value>, Error> {
+    match base {
+        Cow::Borrowed(base) => entry(base, index).map(Cow::Borrowed),
+        Cow::Owned(base) => entry(&base, index).map(|entry| Cow::Owned(entry.clone())),
+    }
+}
+
+/// Returns the element of an array at the integer `index`, counting from the
+/// end if negative, or the entry of an object at the string `index`
+fn entry<'v>(base: &'v Value, index: &Value) -> Result<&'v Value, Error> {
+    match (base, index) {
+        (Value::Array(array), Value::Number(n)) => {
+            let Some(i) = n.as_i64() else {
+                return Err(Error::Index("array", "float"));
+            };
+
+            let position = if i < 0 {
+                usize::try_from(i.unsigned_abs())
+                    .ok()
+                    .and_then(|back| array.len().checked_sub(back))
+            } else {
+                usize::try_from(i).ok()
+            };
+
+            position
+                .and_then(|position| array.get(position))
+                .ok_or(Error::OutOfRange(i, array.len()))
+        }
+        (Value::Object(object), Value::String(key)) => object.get(key).ok_or_else(|| {
+            Error::NoKey(
+                key.clone(),
+                suggest(key, object.keys().map(String::as_str)).map(str::to_owned),
+            )
+        }),
+        _ => Err(Error::Index(type_name(base), type_name(index))),
+    }
+}
+
+/// Whether `value` counts as true for `or`, `and` and `not`. Null, false,
+/// zero and empty values are false
+#[must_use]
+pub fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(array) => !array.is_empty(),
+        Value::Object(object) => !object.is_empty(),
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn apply(op: Op, lhs: &Value, rhs: &Value) -> Result<Value, Error> {
+    let mismatch = || Error::Operands(op, type_name(lhs), type_name(rhs));
+
+    match op {
+        Op::Eq => return Ok(equals(lhs, rhs).into()),
+        Op::Ne => return Ok((!equals(lhs, rhs)).into()),
+        Op::Lt | Op::Le | Op::Gt | Op::Ge => {
+            let ordering = match (lhs, rhs) {
+                (Value::Number(l), Value::Number(r)) => compare(l, r),
+                (Value::String(l), Value::String(r)) => Some(l.cmp(r)),
+                _ => None,
+            }
+            .ok_or_else(mismatch)?;
+
+            return Ok(match op {
+                Op::Lt => ordering.is_lt(),
+                Op::Le => ordering.is_le(),
+                Op::Gt => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            }
+            .into());
+        }
+        _ => {}
+    }
+
+    match (lhs, rhs) {
+        (Value::String(l), Value::String(r)) if op == Op::Add => Ok(format!("{l}{r}").into()),
+        (Value::Number(l), Value::Number(r)) => match (l.as_i64(), r.as_i64()) {
+            (Some(l), Some(r)) => integer(op, l, r),
+            _ => float(
+                op,
+                l.as_f64().ok_or_else(mismatch)?,
+                r.as_f64().ok_or_else(mismatch)?,
+            ),
+        },
+        _ => Err(mismatch()),
+    }
+}
+
+fn equals(lhs: &Value, rhs: &Value) -> bool {
+    match (lhs, rhs) {
+        (Value::Number(l), Value::Number(r)) => compare(l, r).is_some_and(Ordering::is_eq),
+        _ => lhs == rhs,
+    }
+}
+
+fn compare(lhs: &Number, rhs: &Number) -> Option<Ordering> {
+    match (lhs.as_i64(), rhs.as_i64()) {
+        (Some(l), Some(r)) => Some(l.cmp(&r)),
+        _ => lhs.as_f64()?.partial_cmp(&rhs.as_f64()?),
+    }
+}
+
+fn integer(op: Op, lhs: i64, rhs: i64) -> Result<Value, Error> {
+    if matches!(op, Op::Div | Op::Rem) && rhs == 0 {
+        return Err(Error::DivisionByZero);
+    }
+
+    let result = match op {
+        Op::Add => lhs.checked_add(rhs),
+        Op::Sub => lhs.checked_sub(rhs),
+        Op::Mul => lhs.checked_mul(rhs),
+        // Divisions that are not exact give a float, like `7 / 2` is `3.5`
+        #[allow(clippy::cast_precision_loss)]
+        Op::Div if lhs % rhs != 0 => return float(op, lhs as f64, rhs as f64),
+        Op::Div => lhs.checked_div(rhs),
+        _ => lhs.checked_rem(rhs),
+    };
+
+    result.map(Value::from).ok_or(Error::Overflow(op))
+}
+
+fn float(op: Op, lhs: f64, rhs: f64) -> Result<Value, Error> {
+    if matches!(op, Op::Div | Op::Rem) && rhs == 0.0 {
+        return Err(Error::DivisionByZero);
+    }
+
+    let result = match op {
+        Op::Add => lhs + rhs,
+        Op::Sub => lhs - rhs,
+        Op::Mul => lhs * rhs,
+        Op::Div => lhs / rhs,
+        _ => lhs % rhs,
+    };
+
+    Number::from_f64(result)
+        .map(Value::Number)
+        .ok_or(Error::Overflow(op))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use crate::{
+        replacer::{ContentsLexer, Type},
+        values::Values,
+    };
+    use std::path::Path;
+
+    const CONFIG: &str = r#"temple_delimiters: { open: String, close: String } = { open: "{{", close: "}}" }
+name: String = "temple"
+description: String = ""
+summary: String
+count = 3
+author = { name: "ana", age: 30 }
+authors = [{ name: "ana" }, { name: "bob" }]
+field = "age""#;
+
+    fn render(placeholder: &str) -> Result<String, String> {
+        let config = Values::from_str(CONFIG, Path::new("config.tpl")).expect("Valid config");
+        let mut lexer = ContentsLexer::new(placeholder, Path::new("test"), &config)
+            .expect("Delimiters are set");
+        let mut tokens = Vec::new();
+
+        while let Some(token) = lexer.next() {
+            let token = token.expect("Valid token");
+
+            if !matches!(token, Type::PotentialDelim(_) | Type::Raw(_)) {
+                tokens.push((token, lexer.span()));
+            }
+        }
+
+        let expr = parse(placeholder, &tokens, placeholder.len()..placeholder.len())
+            .map_err(|(expected, _)| format!("Expected {expected}"))?;
+        let rendered = expr.render(&config).map_err(|err| err.to_string())?;
+
+        Ok(rendered.into_owned())
+    }
+
+    #[test]
+    fn expressions() {
+        let ok = |placeholder| render(placeholder).expect("Valid expression");
+
+        assert_eq!(ok(r#"{{ description or "TODO" }}"#), "TODO");
+        assert_eq!(ok(r#"{{ name or "TODO" }}"#), "temple");
+        // Keys without a value, declared or not, fall back to the right side
+        assert_eq!(ok(r#"{{ summary or "TODO" }}"#), "TODO");
+        assert_eq!(ok(r#"{{ missing or "TODO" }}"#), "TODO");
+        assert_eq!(ok("{{ missing.field or missing or name }}"), "temple");
+        assert_eq!(
+            render("{{ missing or other }}"),
+            Err("Placeholder uses the undeclared key `other`".to_owned())
+        );
+        assert_eq!(ok("{{ count * 2 + 1 }}"), "7");
+        assert_eq!(ok("{{ count -1 }}"), "2");
+        assert_eq!(ok("{{ count +1 }}"), "4");
+        assert_eq!(ok("{{ count -1.5 }}"), "1.5");
+        assert_eq!(ok("{{ count +1.5 }}"), "4.5");
+        assert_eq!(ok("{{ count+.5 }}"), "3.5");
+        assert_eq!(ok("{{ -(count + 1) * 2 }}"), "-8");
+        assert_eq!(ok("{{ 7 / 2 }}"), "3.5");
+        assert_eq!(ok("{{ 7 % 2 == 1 and not false }}"), "true");
+        assert_eq!(
+            ok(r#"{{ author.age >= 18 and author.name != "bob" }}"#),
+            "true"
+        );
+        assert_eq!(ok(r#"{{ name + "-" + author.name }}"#), "temple-ana");
+        assert_eq!(ok("{{ count == 3.0 }}"), "true");
+        assert_eq!(ok("{{ authors.1.name }}"), "bob");
+        assert_eq!(
+            ok("{{ authors[-2].name + authors[count - 2]['name'] }}"),
+            "anabob"
+        );
+        assert_eq!(ok("{{ author[field] }}"), "30");
+
+        assert_eq!(
+            render("{{ name < 3 }}"),
+            Err("Can not apply `<` to values of type string and number".to_owned())
+        );
+        assert_eq!(render("{{ 1 / 0 }}"), Err("Division by zero".to_owned()));
+        assert_eq!(
+            render("{{ authors[2] }}"),
+            Err("Placeholder accesses the index 2 of an array of length 2".to_owned())
+        );
+        assert_eq!(
+            render("{{ author['nam'] }}"),
+            Err(
+                "Placeholder looks up the key `nam` which the object does not have, did you mean `name`?"
+                    .to_owned()
+            )
+        );
+        assert_eq!(render("{{ (1 + 2 }}"), Err("Expected `)`".to_owned()));
+        assert_eq!(
+            render("{{ 1 2 }}"),
+            Err("Expected an operator or the end of the placeholder".to_owned())
+        );
+    }
+}
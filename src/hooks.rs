@@ -0,0 +1,368 @@
+use anyhow::{anyhow, bail, ensure};
+use std::{
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+use tera::{Map, Value};
+
+use crate::values::{Type, Values};
+
+/// Key of the commands to run before any file is rendered
+pub const PRE_HOOKS: &str = "temple_pre_hooks";
+
+/// Key of the commands to run once every file has been rendered
+pub const POST_HOOKS: &str = "temple_post_hooks";
+
+/// Returns the commands declared under `key`, an empty list if there are none
+///
+/// # Errors
+///
+/// Returns an [`Err`] if the value is not a list of strings
+pub fn commands(values: &Values, key: &str) -> anyhow::Result<Vec<String>> {
+    match values.value_map.get(key) {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::Array(commands)) => commands
+            .iter()
+            .map(|command| {
+                command.as_str().map(ToOwned::to_owned).ok_or(anyhow!(
+                    "Invalid command {command} in '{key}', expected a String"
+                ))
+            })
+            .collect(),
+        Some(value) => bail!("Invalid value {value} for '{key}', expected a list of commands"),
+    }
+}
+
+/// Returns the values as environment variables, each key in upper case.
+/// Strings are exported as is and any other value as JSON
+#[must_use]
+pub fn env_vars(values: &Values) -> Vec<(String, String)> {
+    values
+        .value_map
+        .iter()
+        .filter(|(_, v)| !v.is_null())
+        .map(|(k, v)| {
+            (
+                k.to_uppercase(),
+                v.as_str().map_or_else(|| v.to_string(), ToOwned::to_owned),
+            )
+        })
+        .collect()
+}
+
+/// Runs `command` with the system shell inside `at`, with `values` exported
+/// as environment variables. The output of the command goes straight to the
+/// terminal
+///
+/// # Errors
+///
+/// Returns an [`Err`] if the command can not be started or exits with a
+/// non-zero status
+pub fn run(command: &str, at: &Path, values: &Values) -> anyhow::Result<()> {
+    crate::trace!("Running `{command}` at {}", at.display());
+
+    let status = shell(command)
+        .current_dir(at)
+        .envs(env_vars(values))
+        .status()
+        .map_err(|err| anyhow!("Failed starting `{command}`: {err}"))?;
+
+    ensure!(status.success(), "`{command}` exited with {status}");
+
+    Ok(())
+}
+
+/// Runs the validation `command` inside `at`. The command gets the values as
+/// a JSON object on stdin and may reject them by exiting with a non-zero
+/// status, or replace some of them by printing a JSON object on stdout.
+///
+/// Returns the values the command wants to replace, if any.
+///
+/// # Errors
+///
+/// Returns an [`Err`] with the output of the command if it rejects the
+/// values, or if it prints anything but a JSON object
+pub fn run_pre(
+    command: &str,
+    at: &Path,
+    values: &Values,
+) -> anyhow::Result<Option<Map<String, Value>>> {
+    crate::trace!("Running `{command}` at {}", at.display());
+
+    let mut child = shell(command)
+        .current_dir(at)
+        .envs(env_vars(values))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| anyhow!("Failed starting `{command}`: {err}"))?;
+
+    let input = serde_json::to_vec(&*values.value_map)?;
+    let stdin = child.stdin.take();
+
+    // The input is written while the output is read, a hook that prints
+    // before reading all of it would block both otherwise
+    let output = std::thread::scope(|scope| {
+        if let Some(mut stdin) = stdin {
+            scope.spawn(move || {
+                // The hook may exit without reading its input
+                if let Err(err) = stdin.write_all(&input) {
+                    crate::trace!("Failed writing values to `{command}`: {err}");
+                }
+            });
+        }
+
+        child.wait_with_output()
+    })
+    .map_err(|err| anyhow!("Failed running `{command}`: {err}"))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    if !output.status.success() {
+        let message = [stderr.trim(), stdout.trim()]
+            .into_iter()
+            .filter(|m| !m.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        bail!(
+            "`{command}` rejected the values ({status}){message}",
+            status = output.status,
+            message = if message.is_empty() {
+                String::new()
+            } else {
+                format!(":\n    {}", message.replace('\n', "\n    "))
+            }
+        );
+    }
+
+    if !stderr.trim().is_empty() {
+        crate::warn!("`{command}`: {}", stderr.trim());
+    }
+
+    if stdout.trim().is_empty() {
+        return Ok(None);
+    }
+
+    match serde_json::from_str(&stdout) {
+        Ok(Value::Object(changes)) => Ok(Some(changes)),
+        Ok(value) => bail!("`{command}` printed {value}, expected a JSON object with values"),
+        Err(err) => bail!("`{command}` printed invalid JSON: {err}"),
+    }
+}
+
+/// Replaces the values with the ones returned by a validation hook, checking
+/// them against the declared types
+///
+/// # Errors
+///
+/// Returns an [`Err`] if any of the new values does not match its declared type
+pub fn apply(values: &mut Values, changes: Map<String, Value>) -> anyhow::Result<()> {
+    for (key, value) in changes {
+        let decl_type = values.type_map.get(&key).cloned().unwrap_or(Type::Any);
+        let val_type = Type::from_value(&value, &decl_type);
+
+        ensure!(
+            decl_type.is_equivalent(&val_type),
+            "Invalid value {value} for '{key}', expected {decl_type} but found {val_type}"
+        );
+
+        crate::trace!("Hook replaced value of '{key}' with {value}");
+
+        values.type_map.entry(key.clone()).or_insert(decl_type);
+        values.value_map.insert(key, value);
+    }
+
+    Ok(())
+}
+
+fn shell(command: &str) -> Command {
+    #[cfg(target_os = "windows")]
+    let mut cmd = {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C");
+        cmd
+    };
+
+    #[cfg(not(target_os = "windows"))]
+    let mut cmd = {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c");
+        cmd
+    };
+
+    cmd.arg(command);
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply, commands, env_vars, run, run_pre, POST_HOOKS, PRE_HOOKS};
+    use crate::values::Values;
+    use std::path::Path;
+    use tera::{Map, Value};
+
+    fn values() -> Values {
+        Values::from_str(
+            r#"
+            name: String = "temple"
+            count: Number = 3
+            tags: [String] = ["a"]
+            "#,
+            Path::new("config.tpl"),
+        )
+        .expect("Valid config")
+    }
+
+    #[test]
+    fn hooks_in_config() {
+        let values = Values::from_str(
+            r#"
+            temple_pre_hooks = ['test -n "$NAME"', "true"]
+            temple_post_hooks: [String] = ["git init"]
+            "#,
+            Path::new("config.tpl"),
+        )
+        .expect("Valid config");
+
+        assert_eq!(
+            commands(&values, PRE_HOOKS).expect("Valid hooks"),
+            ["test -n \"$NAME\"", "true"]
+        );
+        assert_eq!(
+            commands(&values, POST_HOOKS).expect("Valid hooks"),
+            ["git init"]
+        );
+        assert!(commands(&self::values(), PRE_HOOKS)
+            .expect("Valid hooks")
+            .is_empty());
+
+        for invalid in [r#"temple_pre_hooks = "true""#, "temple_pre_hooks = [true]"] {
+            let values = Values::from_str(invalid, Path::new("config.tpl")).expect("Valid config");
+            assert!(commands(&values, PRE_HOOKS).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn env_var_names() {
+        let mut vars = env_vars(&values());
+        vars.sort();
+
+        assert_eq!(
+            vars,
+            [
+                ("COUNT".to_owned(), "3".to_owned()),
+                ("NAME".to_owned(), "temple".to_owned()),
+                ("TAGS".to_owned(), r#"["a"]"#.to_owned()),
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn post_hook_env_and_dir() {
+        let dir = tempfile::tempdir().expect("Failed creating temp dir");
+
+        run(
+            r#"printf '%s\n' "$NAME" "$COUNT" "$TAGS" "$(pwd)" > hook.txt"#,
+            dir.path(),
+            &values(),
+        )
+        .expect("Hook succeeded");
+
+        let at = dir.path().canonicalize().expect("Existing dir");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("hook.txt")).expect("Hook wrote file"),
+            format!("temple\n3\n[\"a\"]\n{}\n", at.display())
+        );
+
+        let err = run("exit 2", dir.path(), &values()).expect_err("Hook failed");
+        assert!(err.to_string().contains("exited with"), "{err}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pre_hook_output() {
+        let dir = tempfile::tempdir().expect("Failed creating temp dir");
+        let run = |command| run_pre(command, dir.path(), &values());
+
+        assert_eq!(run("true").expect("Accepted values"), None);
+        assert_eq!(run("echo '  '").expect("Accepted values"), None);
+
+        let changes = run(r#"cat > /dev/null; echo '{ "name": "hooked", "count": 4 }'"#)
+            .expect("Accepted values")
+            .expect("Changed values");
+        assert_eq!(changes["name"], "hooked");
+        assert_eq!(changes["count"], 4);
+
+        // The values are given as a JSON object on stdin
+        let changes = run(r#"sed 's/"temple"/"piped"/'"#)
+            .expect("Accepted values")
+            .expect("Changed values");
+        assert_eq!(changes["name"], "piped");
+        assert_eq!(changes["tags"], serde_json::json!(["a"]));
+
+        let err = run("echo 'bad name' >&2; exit 3").expect_err("Rejected values");
+        assert!(err.to_string().contains("bad name"), "{err}");
+
+        let err = run("echo '[1, 2]'").expect_err("Not an object");
+        assert!(err.to_string().contains("expected a JSON object"), "{err}");
+
+        let err = run("echo '{ name'").expect_err("Invalid JSON");
+        assert!(err.to_string().contains("invalid JSON"), "{err}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pre_hook_output_before_input() {
+        let dir = tempfile::tempdir().expect("Failed creating temp dir");
+        let mut values = values();
+        // Bigger than a pipe, but small enough to be exported to the hook
+        values
+            .value_map
+            .insert("name".to_owned(), Value::String("x".repeat(100_000)));
+
+        // Fills the output before reading the input
+        let command = r#"printf '{"name": "'; head -c 1048576 /dev/zero | tr '\0' y; printf '"}'; cat > /dev/null"#;
+        let changes = run_pre(command, dir.path(), &values)
+            .expect("Accepted values")
+            .expect("Changed values");
+
+        assert_eq!(changes["name"], "y".repeat(1 << 20));
+    }
+
+    #[test]
+    fn apply_checks_types() {
+        let mut values = values();
+        let changes = |json| match serde_json::from_str(json) {
+            Ok(Value::Object(changes)) => changes,
+            _ => Map::new(),
+        };
+
+        apply(
+            &mut values,
+            changes(r#"{ "name": "hooked", "tags": [], "new": 1.5 }"#),
+        )
+        .expect("Valid types");
+        assert_eq!(values.value_map["name"], "hooked");
+        assert_eq!(values.value_map["tags"], serde_json::json!([]));
+        assert_eq!(values.value_map["new"], 1.5);
+        assert!(values.type_map.contains_key("new"));
+
+        for invalid in [
+            r#"{ "name": 1 }"#,
+            r#"{ "count": "4" }"#,
+            r#"{ "tags": ["a", 2] }"#,
+            r#"{ "tags": "a" }"#,
+        ] {
+            let err = apply(&mut values, changes(invalid)).expect_err(invalid);
+            assert!(err.to_string().contains("expected"), "{err}");
+        }
+
+        assert_eq!(values.value_map["name"], "hooked");
+        assert_eq!(values.value_map["count"], 3);
+    }
+}
@@ -5,9 +5,19 @@
 #![allow(clippy::missing_panics_doc)]
 #![deny(clippy::unwrap_used)]
 
+pub mod archive;
 pub mod args;
+pub mod check;
 pub mod config;
 pub mod delimit;
+pub mod error;
+pub mod expr;
+pub mod hooks;
 pub mod log;
+pub mod prompt;
+pub mod registry;
+pub mod render;
 pub mod replacer;
+pub mod source;
+pub mod staging;
 pub mod values;
@@ -0,0 +1,243 @@
+use anyhow::{anyhow, bail, ensure};
+use std::collections::VecDeque;
+use tera::Value;
+
+use crate::values::{Type, Values};
+
+/// Asks for the values and confirmations temple needs while running.
+///
+/// [`Terminal`] asks the user interactively, [`Scripted`] answers from a
+/// list given upfront, for launchers and tests supplying the answers
+/// themselves.
+pub trait Prompter {
+    /// Asks for the string value of `key`
+    fn string(&mut self, key: &str) -> anyhow::Result<String>;
+
+    /// Asks for a value of `key` in the config syntax, which must be of the
+    /// type `expected`
+    fn value(&mut self, key: &str, expected: &Type) -> anyhow::Result<Value>;
+
+    /// Asks for a yes or no answer to `message`, without a default
+    fn bool(&mut self, message: &str) -> anyhow::Result<bool>;
+
+    /// Asks to pick one of `options`, returning its index
+    fn select(&mut self, message: &str, options: &[&str]) -> anyhow::Result<usize>;
+
+    /// Asks to confirm `message`, declined unless the answer is yes
+    fn confirm(&mut self, message: &str) -> anyhow::Result<bool>;
+}
+
+/// Parses `input` as a value of the type `expected`
+fn parse_typed(input: &str, source: &str, expected: &Type) -> anyhow::Result<Value> {
+    let value = Values::parse_value(input, source).map_err(|err| anyhow!(err.message()))?;
+    let found = Type::from_value(&value, expected);
+
+    ensure!(
+        found.is_equivalent(expected),
+        "Mismatching types. Expected {expected} but found {found}"
+    );
+
+    Ok(value)
+}
+
+/// Asks for every key of `values` that has no value, in alphabetical order.
+/// Keys declared with a type are asked a value of that type
+pub fn fill_missing(values: &mut Values, prompter: &mut dyn Prompter) -> anyhow::Result<()> {
+    let mut missing = values
+        .value_map
+        .iter()
+        .filter(|(_, value)| value.is_null())
+        .map(|(key, _)| key.clone())
+        .collect::<Vec<_>>();
+    missing.sort();
+
+    for key in missing {
+        let dtype = values.type_map.get_mut(&key).expect("Every key has a type");
+
+        let value = match dtype {
+            Type::Array(_) | Type::Object(_) | Type::Any | Type::Number => {
+                let value = prompter.value(&key, dtype)?;
+                *dtype = Type::from_value(&value, dtype);
+                value
+            }
+            Type::String => Value::String(prompter.string(&key)?),
+            Type::Bool => {
+                Value::Bool(prompter.bool(&format!("Set bool value of {key:?} to `true`?"))?)
+            }
+            Type::Unknown => bail!(
+                "Keys with unknown data type and no value assigned are not supported: {key:?}"
+            ),
+        };
+
+        values.value_map.insert(key, value);
+    }
+
+    Ok(())
+}
+
+/// Asks the user through the terminal
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Terminal;
+
+impl Prompter for Terminal {
+    fn string(&mut self, key: &str) -> anyhow::Result<String> {
+        inquire::prompt_text(format!("Enter a String value for field {key:?}:"))
+            .map_err(|err| anyhow!(err))
+    }
+
+    fn value(&mut self, key: &str, expected: &Type) -> anyhow::Result<Value> {
+        let validator_type = expected.clone();
+
+        let input = inquire::Text::new(&format!("Enter {expected} value for field {key:?}:"))
+            .with_validator(move |input: &str| {
+                use inquire::validator::Validation;
+
+                Ok(if input.is_empty() {
+                    Validation::Invalid("Empty values not allowed".into())
+                } else {
+                    match parse_typed(input, "stdin", &validator_type) {
+                        Ok(_) => Validation::Valid,
+                        Err(err) => Validation::Invalid(err.to_string().into()),
+                    }
+                })
+            })
+            .prompt()
+            .map_err(|err| anyhow!(err))?;
+
+        parse_typed(&input, "stdin", expected)
+    }
+
+    fn bool(&mut self, message: &str) -> anyhow::Result<bool> {
+        inquire::prompt_confirmation(message).map_err(|err| anyhow!(err))
+    }
+
+    fn select(&mut self, message: &str, options: &[&str]) -> anyhow::Result<usize> {
+        inquire::Select::new(message, options.to_vec())
+            .raw_prompt()
+            .map(|option| option.index)
+            .map_err(|err| anyhow!(err))
+    }
+
+    fn confirm(&mut self, message: &str) -> anyhow::Result<bool> {
+        inquire::Confirm::new(message)
+            .with_default(false)
+            .prompt()
+            .map_err(|err| anyhow!(err))
+    }
+}
+
+/// Answers every prompt with the next answer of a list, failing once they run
+/// out or if an answer is not valid for its prompt.
+///
+/// Values are written in the config syntax, bools and confirmations as `yes`,
+/// `no`, `true` or `false`, and selections as the text of the option picked.
+///
+/// ```
+/// use temple::prompt::{Prompter, Scripted};
+///
+/// let mut prompter = Scripted::new(["temple", "yes"]);
+///
+/// assert_eq!(prompter.string("name").ok(), Some("temple".to_owned()));
+/// assert_eq!(prompter.confirm("Continue?").ok(), Some(true));
+/// assert!(prompter.confirm("Continue?").is_err());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Scripted {
+    answers: VecDeque<String>,
+}
+
+impl Scripted {
+    pub fn new(answers: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            answers: answers.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Answers not used yet
+    #[must_use]
+    pub fn remaining(&self) -> usize {
+        self.answers.len()
+    }
+
+    fn next(&mut self, prompt: &str) -> anyhow::Result<String> {
+        self.answers
+            .pop_front()
+            .ok_or(anyhow!("No answer left for the prompt {prompt:?}"))
+    }
+
+    fn yes_or_no(&mut self, prompt: &str) -> anyhow::Result<bool> {
+        let answer = self.next(prompt)?;
+
+        match answer.to_lowercase().as_str() {
+            "y" | "yes" | "true" => Ok(true),
+            "n" | "no" | "false" => Ok(false),
+            _ => bail!("Answer {answer:?} to the prompt {prompt:?} is not yes or no"),
+        }
+    }
+}
+
+impl Prompter for Scripted {
+    fn string(&mut self, key: &str) -> anyhow::Result<String> {
+        self.next(key)
+    }
+
+    fn value(&mut self, key: &str, expected: &Type) -> anyhow::Result<Value> {
+        let answer = self.next(key)?;
+        parse_typed(&answer, key, expected)
+            .map_err(|err| anyhow!("Invalid answer {answer:?} for {key:?}: {err}"))
+    }
+
+    fn bool(&mut self, message: &str) -> anyhow::Result<bool> {
+        self.yes_or_no(message)
+    }
+
+    fn select(&mut self, message: &str, options: &[&str]) -> anyhow::Result<usize> {
+        let answer = self.next(message)?;
+
+        options
+            .iter()
+            .position(|option| *option == answer)
+            .ok_or(anyhow!(
+                "Answer {answer:?} to the prompt {message:?} is not one of: {}",
+                options.join(", ")
+            ))
+    }
+
+    fn confirm(&mut self, message: &str) -> anyhow::Result<bool> {
+        self.yes_or_no(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fill_missing, Scripted};
+    use crate::values::{Type, Values};
+    use std::path::Path;
+    use tera::Value;
+
+    #[test]
+    fn fill_missing_in_order() {
+        let mut values = Values::from_str(
+            "name: String\nauthors: [String]\npublic: Bool\ncount = 1",
+            Path::new("config.tpl"),
+        )
+        .expect("Valid config");
+
+        let mut prompter = Scripted::new([r#"["ana", "bob"]"#, "temple", "no"]);
+        fill_missing(&mut values, &mut prompter).expect("Valid answers");
+
+        assert_eq!(values.value_map["authors"], Value::from(vec!["ana", "bob"]));
+        assert_eq!(values.value_map["name"], Value::from("temple"));
+        assert_eq!(values.value_map["public"], Value::from(false));
+        assert_eq!(
+            values.type_map["authors"],
+            Type::Array(Box::new(Type::String))
+        );
+        assert_eq!(prompter.remaining(), 0);
+
+        let mut values =
+            Values::from_str("count: Number", Path::new("config.tpl")).expect("Valid config");
+        assert!(fill_missing(&mut values, &mut Scripted::new(["\"three\""])).is_err());
+        assert!(fill_missing(&mut values, &mut Scripted::new(Vec::<String>::new())).is_err());
+    }
+}
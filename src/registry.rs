@@ -0,0 +1,332 @@
+use anyhow::{anyhow, bail, Context};
+use serde::Deserialize;
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use crate::archive::Format;
+
+/// A catalogue of templates and where to get them from
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Index {
+    #[serde(default)]
+    pub templates: Vec<Entry>,
+}
+
+/// A template listed in an [`Index`]
+#[derive(Debug, Clone, Deserialize)]
+pub struct Entry {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Git repository url or path/url of an archive made with `temple export`
+    pub source: String,
+    /// Branch, tag or commit to install when `source` is a git repository
+    #[serde(default)]
+    pub rev: Option<String>,
+}
+
+impl Entry {
+    /// Whether the source is an archive instead of a git repository
+    #[must_use]
+    pub fn is_archive(&self) -> bool {
+        Format::from_path(Path::new(&self.source)).is_ok()
+    }
+
+    /// Whether the name, description or any tag contains `query`, ignoring
+    /// case. An empty query matches every entry
+    #[must_use]
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+
+        self.name.to_lowercase().contains(&query)
+            || self.description.to_lowercase().contains(&query)
+            || self.tags.iter().any(|t| t.to_lowercase().contains(&query))
+    }
+}
+
+impl Index {
+    /// Loads the index at `location`, which may be a path, a `file://` url or
+    /// an `http://` or `https://` url. Indexes ending in `.toml` are read as
+    /// TOML, any other as JSON.
+    ///
+    /// Relative archive and repository paths inside an index are resolved
+    /// from the directory of the index, or from its url for remote indexes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Err`] if the index can not be fetched or has an
+    /// invalid format
+    pub fn load(location: &str) -> anyhow::Result<Self> {
+        crate::trace!("Loading registry index {location}");
+
+        let (contents, dir) = if is_http(location) {
+            (fetch(location)?, None)
+        } else {
+            let path = PathBuf::from(location.trim_start_matches("file://"));
+            let contents = std::fs::read_to_string(&path)
+                .map_err(|err| anyhow!("Failed reading {}: {err}", path.display()))?;
+
+            (
+                contents,
+                Some(path.parent().unwrap_or(Path::new("")).to_path_buf()),
+            )
+        };
+
+        let mut index: Index = if location.to_ascii_lowercase().ends_with(".toml") {
+            toml::from_str(&contents).map_err(|err| anyhow!("Invalid index {location}: {err}"))?
+        } else {
+            serde_json::from_str(&contents)
+                .map_err(|err| anyhow!("Invalid index {location}: {err}"))?
+        };
+
+        for entry in &mut index.templates {
+            if !is_relative(&entry.source) {
+                continue;
+            }
+
+            entry.source = match dir {
+                Some(ref dir) => dir.join(&entry.source).display().to_string(),
+                None => join_url(location, &entry.source),
+            };
+        }
+
+        Ok(index)
+    }
+
+    /// Returns the entries matching `query`, see [`Entry::matches`]
+    #[must_use]
+    pub fn search(&self, query: &str) -> Vec<&Entry> {
+        self.templates.iter().filter(|e| e.matches(query)).collect()
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&Entry> {
+        self.templates.iter().find(|e| e.name == name)
+    }
+}
+
+/// Whether `location` is an `http://` or `https://` url
+#[must_use]
+pub fn is_http(location: &str) -> bool {
+    let location = location.to_ascii_lowercase();
+    location.starts_with("http://") || location.starts_with("https://")
+}
+
+/// Whether `source` is a relative path, instead of an absolute one, an url
+/// or a `user@host:path` git repository
+fn is_relative(source: &str) -> bool {
+    let before_slash = source.split('/').next().unwrap_or_default();
+
+    Path::new(source).is_relative() && !source.contains("://") && !before_slash.contains(':')
+}
+
+/// Resolves the relative `path` against the directory of the file at `url`
+fn join_url(url: &str, path: &str) -> String {
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    let (scheme, rest) = url.split_once("://").unwrap_or(("", url));
+    let (host, dir) = rest.split_once('/').unwrap_or((rest, ""));
+
+    let mut segments = dir.split('/').collect::<Vec<_>>();
+    segments.pop();
+
+    for segment in path.split('/') {
+        match segment {
+            "." => {}
+            ".." => _ = segments.pop(),
+            segment => segments.push(segment),
+        }
+    }
+
+    format!("{scheme}://{host}/{}", segments.join("/"))
+}
+
+/// Downloads `url` into the file `to`
+///
+/// # Errors
+///
+/// Returns an [`Err`] if the request fails or any IO error occurs
+pub fn download(url: &str, to: &Path) -> anyhow::Result<()> {
+    let mut body = Vec::new();
+
+    get(url)?
+        .into_reader()
+        .read_to_end(&mut body)
+        .with_context(|| format!("Failed reading {url}"))?;
+
+    std::fs::write(to, body).map_err(|err| anyhow!("Failed writing {}: {err}", to.display()))
+}
+
+fn fetch(url: &str) -> anyhow::Result<String> {
+    get(url)?
+        .into_string()
+        .with_context(|| format!("Failed reading {url}"))
+}
+
+fn get(url: &str) -> anyhow::Result<ureq::Response> {
+    match ureq::get(url).call() {
+        Ok(response) => Ok(response),
+        Err(ureq::Error::Status(code, _)) => bail!("Failed fetching {url}: status {code}"),
+        Err(err) => bail!("Failed fetching {url}: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_relative, join_url, Index};
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+    };
+
+    const JSON: &str = r#"{
+        "templates": [
+            {
+                "name": "rust-cli",
+                "description": "A command line app",
+                "tags": ["Rust", "cli"],
+                "source": "https://example.com/rust-cli.git",
+                "rev": "v1"
+            },
+            { "name": "site", "tags": ["web"], "source": "site.tar.gz" }
+        ]
+    }"#;
+
+    const TOML: &str = r#"
+        [[templates]]
+        name = "rust-cli"
+        description = "A command line app"
+        tags = ["Rust", "cli"]
+        source = "https://example.com/rust-cli.git"
+        rev = "v1"
+
+        [[templates]]
+        name = "site"
+        tags = ["web"]
+        source = "site.tar.gz"
+    "#;
+
+    #[test]
+    fn load() {
+        let dir = tempfile::tempdir().expect("Failed creating temp dir");
+
+        for (file, contents) in [("index.json", JSON), ("index.TOML", TOML)] {
+            let path = dir.path().join(file);
+            std::fs::write(&path, contents).expect("Failed writing index");
+
+            for location in [
+                path.display().to_string(),
+                format!("file://{}", path.display()),
+            ] {
+                let index = Index::load(&location).expect("Valid index");
+                assert_eq!(index.templates.len(), 2);
+
+                let cli = index.get("rust-cli").expect("Missing entry");
+                assert_eq!(cli.description, "A command line app");
+                assert_eq!(cli.tags, ["Rust", "cli"]);
+                assert_eq!(cli.source, "https://example.com/rust-cli.git");
+                assert_eq!(cli.rev.as_deref(), Some("v1"));
+                assert!(!cli.is_archive());
+
+                // Relative archives are resolved from the index directory,
+                // even if missing so installing them names the resolved path
+                let site = index.get("site").expect("Missing entry");
+                assert_eq!(
+                    site.source,
+                    dir.path().join("site.tar.gz").display().to_string()
+                );
+                assert_eq!(site.description, "");
+                assert_eq!(site.rev, None);
+                assert!(site.is_archive());
+            }
+        }
+
+        let invalid = dir.path().join("invalid.json");
+        std::fs::write(&invalid, TOML).expect("Failed writing index");
+        assert!(Index::load(&invalid.display().to_string()).is_err());
+        assert!(Index::load(&dir.path().join("missing.json").display().to_string()).is_err());
+    }
+
+    #[test]
+    fn load_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed binding");
+        let addr = listener.local_addr().expect("Missing address");
+
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("Failed accepting");
+            let mut request = [0; 1024];
+            _ = stream.read(&mut request).expect("Failed reading request");
+
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{TOML}",
+                TOML.len()
+            )
+            .expect("Failed writing response");
+        });
+
+        let index =
+            Index::load(&format!("http://{addr}/registry/index.toml")).expect("Valid index");
+        server.join().expect("Server panicked");
+
+        // Relative sources of remote indexes are resolved from their url
+        assert_eq!(
+            index.get("site").map(|e| e.source.as_str()),
+            Some(format!("http://{addr}/registry/site.tar.gz").as_str())
+        );
+        assert_eq!(
+            index.get("rust-cli").map(|e| e.source.as_str()),
+            Some("https://example.com/rust-cli.git")
+        );
+    }
+
+    #[test]
+    fn relative_sources() {
+        for (source, relative) in [
+            ("site.tar.gz", true),
+            ("../templates/site", true),
+            ("/srv/site.tar.gz", false),
+            ("file:///srv/site.tar.gz", false),
+            ("https://example.com/site.git", false),
+            ("git@example.com:site.git", false),
+        ] {
+            assert_eq!(is_relative(source), relative, "{source}");
+        }
+
+        let url = "https://example.com/registry/index.json?v=1";
+        assert_eq!(
+            join_url(url, "site.tar.gz"),
+            "https://example.com/registry/site.tar.gz"
+        );
+        assert_eq!(
+            join_url(url, "../archives/./site.zip"),
+            "https://example.com/archives/site.zip"
+        );
+        assert_eq!(
+            join_url("http://localhost:8080", "site.zip"),
+            "http://localhost:8080/site.zip"
+        );
+    }
+
+    #[test]
+    fn search() {
+        let index: Index = serde_json::from_str(JSON).expect("Valid index");
+        let names = |query| {
+            index
+                .search(query)
+                .into_iter()
+                .map(|e| e.name.as_str())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(""), ["rust-cli", "site"]);
+        assert_eq!(names("rust"), ["rust-cli"]);
+        assert_eq!(names("COMMAND"), ["rust-cli"]);
+        assert_eq!(names("WEB"), ["site"]);
+        assert_eq!(names("i"), ["rust-cli", "site"]);
+        assert!(names("python").is_empty());
+    }
+}
@@ -0,0 +1,678 @@
+use anyhow::anyhow;
+use rayon::prelude::*;
+use std::{
+    borrow::Cow,
+    io::{BufRead, BufReader, BufWriter, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+use crate::{
+    config::Template,
+    delimit::FileDelimiters,
+    error::RenderError,
+    expr,
+    replacer::{self, ContentsLexer, DelimiterType, Trim, Type},
+    staging::Staging,
+    values::Values,
+};
+
+/// Size from which the contents read are rendered, unless they end inside a
+/// placeholder or a line. Chunks only end at newlines
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Renders contents, paths and whole templates with a set of values.
+///
+/// Errors found in what is rendered, like undeclared keys or invalid
+/// expressions, are returned as [`RenderError`]s pointing at their location.
+/// The outer [`anyhow::Error`] is left for IO and configuration failures.
+///
+/// ```no_run
+/// # fn main() -> anyhow::Result<()> {
+/// # use std::path::Path;
+/// # let values = temple::values::Values::default();
+/// use temple::render::{Options, Renderer};
+///
+/// let renderer = Renderer::new(&values)?;
+/// let greeting = renderer.render_str("Hello {{ name }}", Path::new("greeting"))?;
+///
+/// let report =
+///     renderer.render_template(Path::new("template"), Path::new("out"), Options::default())?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Renderer<'v> {
+    values: &'v Values,
+    delimiters: FileDelimiters<'v>,
+}
+
+/// How [`Renderer::render_template`] renders
+#[derive(Default)]
+pub struct Options<'a> {
+    /// Number of files rendered in parallel, one per CPU if not set
+    pub jobs: Option<NonZeroUsize>,
+    /// Decides whether existing files are overwritten, asked once with the
+    /// first existing target. Existing files are skipped if not set
+    #[allow(clippy::type_complexity)]
+    pub overwrite: Option<Box<dyn FnMut(&Path) -> anyhow::Result<bool> + 'a>>,
+}
+
+/// Outcome of [`Renderer::render_template`]
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Files written, relative to the target
+    pub rendered: Vec<PathBuf>,
+    /// Existing files left untouched, relative to the target
+    pub skipped: Vec<PathBuf>,
+    /// Errors found in the template, in order. Nothing is written if there
+    /// are any
+    pub errors: Vec<RenderError>,
+}
+
+impl<'v> Renderer<'v> {
+    /// Returns a renderer of `values`, failing if the globs of
+    /// `temple_delimiters_for` are not valid
+    pub fn new(values: &'v Values) -> anyhow::Result<Self> {
+        Ok(Self {
+            values,
+            delimiters: FileDelimiters::new(values)?,
+        })
+    }
+
+    /// Renders `contents` with the configured delimiters, `origin` being the
+    /// path the errors point at. The inner [`Err`] holds the errors found in
+    /// the contents
+    pub fn render_str(
+        &self,
+        contents: &str,
+        origin: &Path,
+    ) -> anyhow::Result<Result<String, Vec<RenderError>>> {
+        let mut lexer = ContentsLexer::new(contents, origin, self.values)?;
+
+        let mut rendered = Vec::new();
+        let errors = render_lexed(&mut lexer, self.values, &mut rendered)?;
+
+        if !errors.is_empty() {
+            return Ok(Err(errors));
+        }
+
+        Ok(Ok(
+            String::from_utf8(rendered).expect("Rendered from valid UTF-8")
+        ))
+    }
+
+    /// Renders the path `render`, relative to the template at `origin`
+    pub fn render_path(
+        &self,
+        render: &Path,
+        origin: &Path,
+    ) -> anyhow::Result<Result<PathBuf, Vec<RenderError>>> {
+        Ok(self
+            .render_str(&render.display().to_string(), origin)?
+            .map(PathBuf::from))
+    }
+
+    /// Streams the contents of `reader` into `writer` with the placeholders
+    /// replaced. The contents are read by lines and rendered in chunks of
+    /// about [`CHUNK_SIZE`], never splitting a placeholder. Lines are not
+    /// split either, a line longer than a chunk, like minified JSON, is read
+    /// whole into memory.
+    ///
+    /// The delimiters are the ones of the header in the first line, which is
+    /// not written, or the ones configured for `relative`, the path of the
+    /// file inside the template.
+    ///
+    /// Returns every error found in the contents, nothing more is written
+    /// once one is found.
+    pub fn render_to(
+        &self,
+        mut reader: impl BufRead,
+        writer: &mut impl Write,
+        origin: &Path,
+        relative: &Path,
+    ) -> anyhow::Result<Vec<RenderError>> {
+        let mut read_line = |buff: &mut String| {
+            reader.read_line(buff).map_err(|err| {
+                anyhow!(
+                    "Error while reading origin path {}: {err}",
+                    origin.display()
+                )
+            })
+        };
+
+        let mut header = String::new();
+        read_line(&mut header)?;
+
+        let (delimiters, is_header) = self.delimiters.for_file(relative, &header)?;
+        let mut first_line = if is_header { 2 } else { 1 };
+        let mut errors = Vec::new();
+        let mut chunk = String::new();
+        let mut check_at = CHUNK_SIZE;
+        let mut trim = Trim::Nothing;
+        let trim_blocks = replacer::trim_blocks(self.values);
+
+        if !is_header {
+            chunk.push_str(&header);
+        }
+
+        loop {
+            let read = read_line(&mut chunk)?;
+
+            if read != 0 && chunk.len() < check_at {
+                continue;
+            }
+
+            // Placeholders, comments and raw blocks are kept whole inside a
+            // chunk. The chunk grows twice as big before checking it again
+            if read != 0 && replacer::find_unclosed(&delimiters, &chunk).is_some() {
+                check_at = chunk.len() * 2;
+                continue;
+            }
+
+            // The trailing whitespace is left for the next chunk, where a trim
+            // marker may remove it
+            let split = if read == 0 {
+                chunk.len()
+            } else {
+                chunk.trim_end().len()
+            };
+
+            if split > 0 {
+                let mut lexer = ContentsLexer::with_delimiters(&chunk[..split], origin, delimiters)
+                    .starting_at_line(first_line)
+                    .trimming_blocks(trim_blocks)
+                    .trimming_start(trim);
+
+                if errors.is_empty() {
+                    errors = render_lexed(&mut lexer, self.values, writer)?;
+                } else {
+                    errors.extend(render_lexed(&mut lexer, self.values, &mut std::io::sink())?);
+                }
+
+                trim = lexer.trim;
+            }
+
+            if read == 0 {
+                break;
+            }
+
+            first_line += chunk[..split].matches('\n').count();
+            chunk.drain(..split);
+            check_at = CHUNK_SIZE;
+        }
+
+        Ok(errors)
+    }
+
+    /// Renders every file of the template at `template` into `target`.
+    ///
+    /// The files are rendered in parallel into a staging directory and only
+    /// moved into `target` once every one of them rendered, a failure leaves
+    /// the target untouched.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Err`] if any IO error occurs, errors in the template are
+    /// returned in the [`Report`]
+    pub fn render_template(
+        &self,
+        template: &Path,
+        target: &Path,
+        mut options: Options<'_>,
+    ) -> anyhow::Result<Report> {
+        // Gather the files first to render them in parallel, the results
+        // keep the order of the walk
+        let mut files = Vec::new();
+
+        let walker = WalkDir::new(template)
+            .min_depth(1)
+            .sort_by_file_name()
+            .into_iter();
+        for entry in walker.filter_entry(Template::is_rendered) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    crate::warn!("Error with path: {}", err);
+                    continue;
+                }
+            };
+
+            if entry.file_type().is_dir() {
+                continue;
+            }
+
+            let relative = entry
+                .path()
+                .strip_prefix(template)
+                .map_err(|err| anyhow!("Failed stripping prefix: {err}"))?;
+
+            crate::trace!(
+                "Rendering: Render of {} into {}",
+                entry.path().display(),
+                target.join(relative).display()
+            );
+
+            files.push((entry.path().to_path_buf(), relative.to_path_buf()));
+        }
+
+        let name = target
+            .file_name()
+            .map_or(Cow::Borrowed("render"), |name| name.to_string_lossy());
+        let mut staging = Staging::new(&name)?;
+
+        // Zero threads makes rayon use one per CPU
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(options.jobs.map_or(0, NonZeroUsize::get))
+            .build()
+            .map_err(|err| anyhow!("Failed starting the render threads: {err}"))?;
+
+        let rendered = pool.install(|| {
+            files
+                .par_iter()
+                .enumerate()
+                .map(|(i, (origin, relative))| self.render_file(i, origin, relative, &staging))
+                .collect::<Vec<_>>()
+        });
+
+        let mut report = Report::default();
+        let mut overwrite = None;
+
+        for result in rendered {
+            // Keep going after an error to report every error of the template at once
+            let (staged, relative) = match result? {
+                Ok(_) if !report.errors.is_empty() => continue,
+                Ok(rendered) => rendered,
+                Err(errors) => {
+                    report.errors.extend(errors);
+                    continue;
+                }
+            };
+
+            let path = target.join(&relative);
+
+            if path.exists() {
+                if overwrite.is_none() {
+                    overwrite = Some(match options.overwrite.as_mut() {
+                        Some(ask) => ask(&path)?,
+                        None => false,
+                    });
+                }
+
+                if overwrite == Some(false) {
+                    report.skipped.push(relative);
+                    continue;
+                }
+            }
+
+            staging.add(staged, &path);
+            report.rendered.push(relative);
+        }
+
+        if report.errors.is_empty() {
+            staging.commit()?;
+        } else {
+            report.rendered.clear();
+            report.skipped.clear();
+        }
+
+        Ok(report)
+    }
+
+    /// Renders the path of the file at `origin`, `relative` to the template,
+    /// and streams the rendered contents into the `i`-th staged file.
+    /// The inner [`Err`] holds the errors found in the template
+    fn render_file(
+        &self,
+        i: usize,
+        origin: &Path,
+        relative: &Path,
+        staging: &Staging,
+    ) -> anyhow::Result<Result<(PathBuf, PathBuf), Vec<RenderError>>> {
+        let reader = std::fs::File::open(origin)
+            .map(BufReader::new)
+            .map_err(|err| anyhow!("Error with origin path {}: {err}", origin.display()))?;
+
+        let (staged, file) = staging.create(i)?;
+        let mut writer = BufWriter::new(file);
+
+        let errors = self.render_to(reader, &mut writer, origin, relative)?;
+        let target = self.render_path(relative, origin)?;
+
+        writer
+            .flush()
+            .map_err(|err| anyhow!("Failed writing {}: {err}", staged.display()))?;
+
+        Ok(match target {
+            Ok(target) if errors.is_empty() => Ok((staged, target)),
+            Ok(_) => Err(errors),
+            Err(mut path_errors) => {
+                path_errors.extend(errors);
+                Err(path_errors)
+            }
+        })
+    }
+}
+
+/// Writes the contents being lexed into `writer` with their placeholders
+/// replaced, returning every error found in them, in order and pointing at
+/// their location. Nothing more is written once an error is found
+fn render_lexed(
+    lexer: &mut ContentsLexer<'_>,
+    values: &Values,
+    writer: &mut impl Write,
+) -> anyhow::Result<Vec<RenderError>> {
+    let mut errors = Vec::new();
+    // Tokens of the placeholder being lexed, if inside one, and whether any
+    // of them was invalid
+    let mut placeholder: Option<(Vec<_>, bool)> = None;
+
+    while let Some(token) = lexer.next() {
+        let span = lexer.span();
+
+        let token = match token {
+            Err(err @ RenderError::InvalidToken { .. }) => {
+                errors.push(err);
+
+                if let Some((_, invalid)) = placeholder.as_mut() {
+                    *invalid = true;
+                }
+                continue;
+            }
+            Err(err) => {
+                // The lexer can not move past an unclosed delimiter
+                errors.push(err);
+                break;
+            }
+            Ok(token) => token,
+        };
+
+        crate::trace!(
+            "Lexer: {:?}: {}: {}: {token:?}",
+            span,
+            lexer.location(span.clone()),
+            lexer.slice(),
+        );
+
+        let contents = match (token, placeholder.as_mut()) {
+            (Type::PotentialDelim(DelimiterType::DelimitOpen), _) => {
+                placeholder = Some((Vec::new(), false));
+                continue;
+            }
+            (Type::PotentialDelim(DelimiterType::DelimitClose), _) => {
+                let Some((tokens, false)) = placeholder.take() else {
+                    continue;
+                };
+
+                match evaluate(lexer.content, &tokens, span, values) {
+                    Ok(contents) => contents,
+                    Err(Failure::Syntax(expected, span)) => {
+                        errors.push(RenderError::Syntax {
+                            location: lexer.location(span),
+                            expected,
+                        });
+                        continue;
+                    }
+                    Err(Failure::Evaluation(err, span)) => {
+                        errors.push(RenderError::evaluating(lexer.location(span), err));
+                        continue;
+                    }
+                }
+            }
+            (Type::Comment(_), Some(_)) => continue,
+            (token, Some((tokens, _))) => {
+                tokens.push((token, span));
+                continue;
+            }
+            (Type::Raw(blob), None) => Cow::Borrowed(blob),
+            (_, None) => continue,
+        };
+
+        if errors.is_empty() {
+            writer
+                .write_all(contents.as_bytes())
+                .map_err(|err| anyhow!("Error writing: {err}"))?;
+        }
+    }
+
+    Ok(errors)
+}
+
+/// Why a placeholder could not be replaced, with the span of the error
+enum Failure {
+    /// Invalid expression, with what was expected
+    Syntax(String, logos::Span),
+    Evaluation(expr::Error, logos::Span),
+}
+
+/// Returns the contents the placeholder made of `tokens`, closed by the
+/// delimiter at `close`, is replaced with. Both spans point into `source`
+fn evaluate<'a>(
+    source: &str,
+    tokens: &[(Type<'a>, logos::Span)],
+    close: logos::Span,
+    values: &'a Values,
+) -> Result<Cow<'a, str>, Failure> {
+    let (Some((_, first)), Some((_, last))) = (tokens.first(), tokens.last()) else {
+        return Ok(Cow::Borrowed(""));
+    };
+    let span = first.start..last.end;
+
+    expr::parse(source, tokens, close)
+        .map_err(|(expected, span)| Failure::Syntax(expected, span))?
+        .render(values)
+        .map_err(|err| Failure::Evaluation(err, span))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Options, Renderer, CHUNK_SIZE};
+    use crate::values::Values;
+    use std::{fmt::Write as _, num::NonZeroUsize, path::Path};
+
+    const CONFIG: &str = r#"temple_delimiters = { open: "{{", close: "}}" }
+name = "temple""#;
+
+    fn values() -> Values {
+        Values::from_str(CONFIG, Path::new("config.tpl")).expect("Valid config")
+    }
+
+    /// Creates a template with a file per name in `files`, in which the
+    /// ones in `invalid` use an undeclared key
+    fn template(dir: &Path, files: &[&str], invalid: &[&str]) {
+        std::fs::create_dir_all(dir.join("sub")).expect("Failed creating template");
+
+        for file in files {
+            let contents = if invalid.contains(file) {
+                format!("{{{{ name }}}}\n{{{{ missing_{file} }}}}\n")
+            } else {
+                format!("{{{{ name }}}} {file}\n")
+            };
+
+            std::fs::write(dir.join(file), contents).expect("Failed writing file");
+        }
+    }
+
+    fn render_to(renderer: &Renderer<'_>, contents: &str) -> (String, Vec<(usize, String)>) {
+        let mut out = Vec::new();
+        let errors = renderer
+            .render_to(
+                contents.as_bytes(),
+                &mut out,
+                Path::new("file"),
+                Path::new("file"),
+            )
+            .expect("Failed rendering");
+
+        (
+            String::from_utf8(out).expect("Rendered valid UTF-8"),
+            errors
+                .iter()
+                .map(|err| (err.location().line, err.location().source.clone()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn tags_across_chunks() {
+        let values = values();
+        let renderer = Renderer::new(&values).expect("Valid delimiters");
+
+        let tail =
+            "{{\n name\n}}|{{#\n a comment {{ name }}\n#}}|\n   {{- name -}}   \n|{{ name }}\n";
+
+        // The filler moves the boundary of the first chunk over every byte of
+        // the tags that follow it
+        for len in CHUNK_SIZE - tail.len() - 2..CHUNK_SIZE + 2 {
+            let contents = format!("{}\n{tail}", "x".repeat(len));
+
+            let (output, errors) = render_to(&renderer, &contents);
+            assert_eq!(errors, []);
+            assert_eq!(&output[..len], &contents[..len]);
+            assert_eq!(&output[len..], "\ntemple||temple|temple\n");
+
+            assert_eq!(
+                renderer
+                    .render_str(&contents, Path::new("file"))
+                    .expect("Failed rendering"),
+                Ok(output)
+            );
+        }
+    }
+
+    #[test]
+    fn line_numbers_in_later_chunks() {
+        let values = values();
+        let renderer = Renderer::new(&values).expect("Valid delimiters");
+
+        // About three chunks of lines
+        let lines = 3 * CHUNK_SIZE / 8;
+        let mut contents = String::new();
+        for i in 1..=lines {
+            if i % 10_000 == 0 {
+                writeln!(contents, "{{{{ missing_{i} }}}}").expect("Failed writing");
+            } else {
+                contents.push_str("line {{ name }}\n");
+            }
+        }
+
+        let expected = (1..=lines)
+            .filter(|i| i % 10_000 == 0)
+            .map(|i| (i, format!("{{{{ missing_{i} }}}}")))
+            .collect::<Vec<_>>();
+        assert_eq!(expected.len(), 2);
+
+        let (_, errors) = render_to(&renderer, &contents);
+        assert_eq!(errors, expected);
+
+        // The lines after a header are counted from the second one
+        let header = "# temple_delimiters: [[ ]]\n";
+        let (output, errors) = render_to(
+            &renderer,
+            &format!(
+                "{header}{}",
+                contents.replace("{{", "[[").replace("}}", "]]")
+            ),
+        );
+        assert!(output.starts_with("line temple\n"));
+        assert_eq!(
+            errors,
+            expected
+                .iter()
+                .map(|(line, source)| (line + 1, source.replace("{{", "[[").replace("}}", "]]")))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn errors_in_order_and_nothing_written() {
+        let dir = tempfile::tempdir().expect("Failed creating temp dir");
+        let files = (0..24).map(|i| format!("sub/{i:02}")).collect::<Vec<_>>();
+        let files = files.iter().map(String::as_str).collect::<Vec<_>>();
+        template(
+            &dir.path().join("tpl"),
+            &files,
+            &["sub/03", "sub/11", "sub/20"],
+        );
+
+        let target = dir.path().join("out");
+        std::fs::create_dir_all(target.join("sub")).expect("Failed creating target");
+        std::fs::write(target.join("sub/00"), "old").expect("Failed writing file");
+
+        let values = values();
+        let renderer = Renderer::new(&values).expect("Valid delimiters");
+
+        let mut reported = Vec::new();
+
+        for jobs in [1, 2, 8] {
+            let options = Options {
+                jobs: NonZeroUsize::new(jobs),
+                overwrite: Some(Box::new(|_| Ok(true))),
+            };
+            let report = renderer
+                .render_template(&dir.path().join("tpl"), &target, options)
+                .expect("Failed rendering");
+
+            assert!(report.rendered.is_empty());
+            assert!(report.skipped.is_empty());
+
+            reported.push(
+                report
+                    .errors
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>(),
+            );
+        }
+
+        assert_eq!(reported[0].len(), 3);
+        for (error, file) in reported[0].iter().zip(["sub/03", "sub/11", "sub/20"]) {
+            assert!(error.starts_with(&format!(
+                "{}:2:",
+                dir.path().join("tpl").join(file).display()
+            )));
+        }
+        assert!(reported.iter().all(|errors| *errors == reported[0]));
+
+        // The target is left as it was
+        let written = walkdir::WalkDir::new(&target)
+            .min_depth(1)
+            .into_iter()
+            .map(|entry| entry.expect("Valid entry").into_path())
+            .collect::<Vec<_>>();
+        assert_eq!(written, [target.join("sub"), target.join("sub/00")]);
+        assert_eq!(
+            std::fs::read_to_string(target.join("sub/00")).expect("Failed reading file"),
+            "old"
+        );
+    }
+
+    #[test]
+    fn skips_existing_files() {
+        let dir = tempfile::tempdir().expect("Failed creating temp dir");
+        template(&dir.path().join("tpl"), &["a", "sub/b"], &[]);
+
+        let target = dir.path().join("out");
+        std::fs::create_dir_all(&target).expect("Failed creating target");
+        std::fs::write(target.join("a"), "old").expect("Failed writing file");
+
+        let values = values();
+        let report = Renderer::new(&values)
+            .expect("Valid delimiters")
+            .render_template(&dir.path().join("tpl"), &target, Options::default())
+            .expect("Failed rendering");
+
+        assert!(report.errors.is_empty());
+        assert_eq!(report.skipped, [Path::new("a")]);
+        assert_eq!(report.rendered, [Path::new("sub/b")]);
+        assert_eq!(
+            std::fs::read_to_string(target.join("a")).expect("Failed reading file"),
+            "old"
+        );
+        assert_eq!(
+            std::fs::read_to_string(target.join("sub/b")).expect("Failed reading file"),
+            "temple sub/b\n"
+        );
+    }
+}
@@ -1,26 +1,71 @@
-use anyhow::{anyhow, Result};
 use logos::{Logos, Span};
-use owo_colors::OwoColorize;
-use std::{borrow::Cow, fmt::Write, path::Path, sync::OnceLock};
+use std::path::Path;
+
+use crate::{
+    delimit::Delimiters,
+    error::{Location, RenderError},
+    values::Values,
+};
+
+/// Tag opening a block whose contents are output untouched
+pub const RAW: &str = "raw";
+
+/// Tag closing a [`RAW`] block
+pub const ENDRAW: &str = "endraw";
+
+/// Key of the option that removes the first newline after a block tag
+pub const TRIM_BLOCKS: &str = "temple_trim_blocks";
+
+/// Marker right inside a delimiter that trims the whitespace on its side,
+/// like `{{- name -}}`
+pub const TRIM_MARKER: char = '-';
+
+/// Marker right inside both delimiters of a comment, like `{{# note #}}`
+pub const COMMENT: char = '#';
+
+/// Whitespace to remove from the start of the next raw text, from the least
+/// to the most removed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Trim {
+    #[default]
+    Nothing,
+    Newline,
+    Whitespace,
+}
 
-use crate::{delimit::Delimiters, values::Values};
+impl Trim {
+    fn apply(self, raw: &str) -> &str {
+        match self {
+            Trim::Nothing => raw,
+            Trim::Newline => raw
+                .strip_prefix("\r\n")
+                .or_else(|| raw.strip_prefix('\n'))
+                .unwrap_or(raw),
+            Trim::Whitespace => raw.trim_start(),
+        }
+    }
+}
 
+#[allow(clippy::struct_excessive_bools)]
 pub struct ContentsLexer<'i> {
     pub in_delimiter: bool,
     pub indicators: Delimiters<'i>,
     pub origin: &'i Path,
     pub state: logos::Lexer<'i, Type<'i>>,
     pub content: &'i str,
-    pub next: Option<(Result<Type<'i>, anyhow::Error>, Span)>,
+    pub next: Option<(Result<Type<'i>, RenderError>, Span)>,
     pub returned_raw: bool,
     pub returned_close: bool,
+    pub first_line: usize,
+    pub escaped: bool,
+    pub trim: Trim,
+    pub trim_blocks: bool,
 }
 
-static DELIMITERS: OnceLock<(String, String)> = OnceLock::new();
-
 #[derive(Logos, Debug, PartialEq, Clone, Copy)]
 #[logos(
     error = (),
+    extras = (&'s str, &'s str),
     skip r"[ \t\n\f]+"
 )]
 pub enum Type<'i> {
@@ -42,10 +87,7 @@ pub enum Type<'i> {
     #[regex(r#"(?i:true)"#, |_| true)]
     Bool(bool),
 
-    #[token("]")]
     SqClose,
-
-    #[token("[")]
     SqOpen,
 
     #[regex("(?i:if)")]
@@ -54,20 +96,45 @@ pub enum Type<'i> {
     KwFor,
     #[regex("(?i:in)")]
     KwIn,
-    #[token("==")]
+    #[regex("(?i:and)")]
+    KwAnd,
+    #[regex("(?i:or)")]
+    KwOr,
+    #[regex("(?i:not)")]
+    KwNot,
     Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    ParOpen,
+    ParClose,
     #[regex("(?i:[a-z][_a-z0-9]*)", priority = 1)]
     Ident(&'i str),
-    #[regex("(?i:[a-z][_a-z0-9]*)([.](?i:[a-z][_a-z0-9]*))+", priority = 2)]
+    #[regex(
+        "(?i:[a-z][_a-z0-9]*)([.]((?i:[a-z][_a-z0-9]*)|[0-9]+))+",
+        priority = 2
+    )]
     IdentWithField(&'i str),
+    /// Field accessed on the result of an index, like `.email` in `authors[0].email`
+    #[regex("[.](?i:[a-z][_a-z0-9]*)", |lex| &lex.slice()[1..])]
+    Field(&'i str),
     DelimitOpen,
     DelimitClose,
     #[regex("#[^\n]*", |lex| lex.slice())]
     Comment(&'i str),
     Raw(&'i str),
-
-    #[regex(r##"[^ \t\n\f][^ \t\n\f]"##, delimiter, priority = 0)]
     PotentialDelim(DelimiterType),
+
+    /// Any other character, lexed by [`symbol`] as a delimiter or an operator
+    #[regex(r"[^ \t\n\f]", symbol, priority = 0)]
+    Symbol,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -76,46 +143,108 @@ pub enum DelimiterType {
     DelimitClose,
 }
 
-fn delimiter<'i>(lex: &mut logos::Lexer<'i, Type<'i>>) -> logos::FilterResult<DelimiterType, ()> {
-    let (ref start, ref end) = DELIMITERS.get().expect("Set on init");
-    if lex.slice().starts_with(&start[..2]) && lex.remainder().starts_with(&start[2..]) {
-        lex.bump(start.len() - 2);
-        logos::FilterResult::Emit(DelimiterType::DelimitOpen)
-    } else if lex.slice().starts_with(&end[..2]) && lex.remainder().starts_with(&end[2..]) {
-        lex.bump(end.len() - 2);
-        logos::FilterResult::Emit(DelimiterType::DelimitClose)
+/// Recognises the open and close delimiters of the lexer, kept in its extras
+/// so that lexers with different delimiters can run at the same time, and
+/// the operators. Delimiters are checked first as they may start with the
+/// same characters as an operator
+fn symbol<'i>(lex: &mut logos::Lexer<'i, Type<'i>>) -> logos::FilterResult<Type<'i>, ()> {
+    let (open, close) = lex.extras;
+    let rem = &lex.source()[lex.span().start..];
+
+    let (token, len) = if let Some(inner) = rem.strip_prefix(open) {
+        let trim = usize::from(inner.starts_with(TRIM_MARKER));
+        (
+            Type::PotentialDelim(DelimiterType::DelimitOpen),
+            open.len() + trim,
+        )
+    } else if rem.starts_with(close) {
+        (
+            Type::PotentialDelim(DelimiterType::DelimitClose),
+            close.len(),
+        )
+    } else if rem.starts_with(TRIM_MARKER) && rem[1..].starts_with(close) {
+        (
+            Type::PotentialDelim(DelimiterType::DelimitClose),
+            close.len() + 1,
+        )
     } else {
-        logos::FilterResult::Error(())
-    }
+        match rem.get(..2).unwrap_or(rem) {
+            "==" => (Type::Eq, 2),
+            "!=" => (Type::Ne, 2),
+            "<=" => (Type::Le, 2),
+            ">=" => (Type::Ge, 2),
+            _ => match lex.slice() {
+                "<" => (Type::Lt, 1),
+                ">" => (Type::Gt, 1),
+                "+" => (Type::Plus, 1),
+                "-" => (Type::Minus, 1),
+                "*" => (Type::Star, 1),
+                "/" => (Type::Slash, 1),
+                "%" => (Type::Percent, 1),
+                "(" => (Type::ParOpen, 1),
+                ")" => (Type::ParClose, 1),
+                "[" => (Type::SqOpen, 1),
+                "]" => (Type::SqClose, 1),
+                _ => return logos::FilterResult::Error(()),
+            },
+        }
+    };
+
+    lex.bump(len - lex.slice().len());
+    logos::FilterResult::Emit(token)
 }
 
 impl<'i> ContentsLexer<'i> {
     pub fn new(s: &'i str, path: &'i Path, config: &'i Values) -> anyhow::Result<Self> {
-        let indicators: Delimiters<'_> = config
-            .value_map
-            .get("temple_delimiters")
-            .ok_or(anyhow!(
-                "Delimiters must be set with the identifier 'temple_delimiters'"
-            ))?
-            .try_into()?;
-
-        _ = DELIMITERS
-            .set((
-                indicators.delimiters().0.to_owned(),
-                indicators.delimiters().1.to_owned(),
-            ))
-            .map_err(|e| crate::trace!("Already set: {e:?}"));
-
-        Ok(ContentsLexer {
+        Ok(
+            Self::with_delimiters(s, path, Delimiters::from_config(config)?)
+                .trimming_blocks(trim_blocks(config)),
+        )
+    }
+
+    /// Lexes `s` with `indicators` instead of the delimiters of the config
+    #[must_use]
+    pub fn with_delimiters(s: &'i str, path: &'i Path, indicators: Delimiters<'i>) -> Self {
+        let state = Type::lexer_with_extras(s, (indicators.0 .0, indicators.1 .0));
+
+        ContentsLexer {
             next: None,
             content: s,
             in_delimiter: false,
             indicators,
-            state: Type::lexer(s),
+            state,
             origin: path,
             returned_raw: false,
             returned_close: false,
-        })
+            first_line: 1,
+            escaped: false,
+            trim: Trim::Nothing,
+            trim_blocks: false,
+        }
+    }
+
+    /// Sets whether the first newline after a block tag is removed, see
+    /// [`trim_blocks`]
+    #[must_use]
+    pub fn trimming_blocks(mut self, trim_blocks: bool) -> Self {
+        self.trim_blocks = trim_blocks;
+        self
+    }
+
+    /// Sets the whitespace to trim from the start of the contents, for
+    /// contents lexed in chunks that follow a tag with a trim marker
+    #[must_use]
+    pub fn trimming_start(mut self, trim: Trim) -> Self {
+        self.trim = trim;
+        self
+    }
+
+    /// Sets the line of the file where the contents start, for contents
+    /// lexed in chunks
+    #[must_use]
+    pub fn starting_at_line(mut self, line: usize) -> Self {
+        self.first_line = line;
+        self
     }
 }
 
@@ -133,142 +262,197 @@ impl<'i> std::ops::DerefMut for ContentsLexer<'i> {
     }
 }
 
-pub struct Replaced<'a>(Vec<Cow<'a, str>>);
-
-impl Replaced<'_> {
-    pub fn write_to_file(&self, mut target: impl std::io::Write) -> Result<()> {
-        for r in self.get_iter() {
-            match r {
-                Cow::Owned(slice) => target.write_all(slice.as_bytes())?,
-                Cow::Borrowed(slice) => target.write_all(slice.as_bytes())?,
-            }
-        }
+/// Returns the candidate closest to `name`, if any is close enough to be a
+/// likely typo
+#[must_use]
+pub fn suggest<'c>(name: &str, candidates: impl IntoIterator<Item = &'c str>) -> Option<&'c str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+
+    candidates
+        .into_iter()
+        .map(|candidate| (strsim::osa_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
 
-        Ok(())
-    }
+impl ContentsLexer<'_> {
+    #[must_use]
+    pub fn span(&self) -> Span {
+        if self.returned_raw {
+            let mut span = self.state.span();
+            span.start += self.indicators.delimiters().1.len();
 
-    pub fn get_iter(&self) -> std::slice::Iter<'_, Cow<'_, str>> {
-        self.0.iter()
+            span
+        } else {
+            self.state.span()
+        }
     }
 
-    pub fn extend_str(&self, t: &mut String) -> Result<()> {
-        t.clear();
-        for r in self.get_iter() {
-            match r {
-                Cow::Owned(slice) => t.write_str(slice)?,
-                Cow::Borrowed(slice) => t.write_str(slice)?,
-            }
+    #[must_use]
+    pub fn slice(&self) -> &str {
+        if self.returned_raw {
+            &self.state.slice()[self.indicators.delimiters().1.len()..]
+        } else {
+            self.state.slice()
         }
-        Ok(())
     }
 
+    /// Returns the location of the byte `span` of the contents
     #[must_use]
-    pub fn get_string(self) -> String {
-        self.0.concat()
+    pub fn location(&self, span: Span) -> Location {
+        Location::new(self.origin, self.content, span, self.first_line)
     }
 }
 
-pub struct Location(Span, usize);
+impl<'i> ContentsLexer<'i> {
+    /// Position of the next open delimiter that is not escaped, if any
+    fn find_start(&mut self) -> Option<usize> {
+        // The delimiter at the start was escaped, it is plain text
+        let from = if std::mem::take(&mut self.escaped) {
+            self.indicators.0.len()
+        } else {
+            0
+        };
 
-impl From<(Span, usize)> for Location {
-    fn from((span, line): (Span, usize)) -> Self {
-        Location(span, line)
+        self.indicators
+            .find_start(self.remainder(), from)
+            .map(|n| n + from)
     }
-}
 
-impl std::fmt::Display for Location {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}:{}-{}", self.1, self.0.start, self.0.end)
-    }
-}
+    /// Returns the next `len` bytes as raw text, skipping `skip` more bytes.
+    /// The trailing whitespace is removed with `trim_end`, for text followed
+    /// by a tag with a trim marker
+    fn raw(&mut self, len: usize, skip: usize, trim_end: bool) -> Type<'i> {
+        let raw = std::mem::take(&mut self.trim).apply(&self.remainder()[..len]);
+        let raw = if trim_end { raw.trim_end() } else { raw };
 
-struct Underlined(usize);
-
-impl std::fmt::Display for Underlined {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            "^".repeat(self.0)
-                .if_supports_color(owo_colors::Stream::Stdout, |s| {
-                    s.style(owo_colors::Style::new().bold().yellow())
-                }),
-        )
+        if self.returned_close {
+            self.returned_raw = true;
+            self.returned_close = false;
+        }
+
+        self.bump(len + skip);
+
+        Type::Raw(raw)
     }
 }
 
 impl ContentsLexer<'_> {
-    fn get_line(&self, line: usize) -> &str {
-        self.content.lines().nth(line - 1).unwrap_or_default()
+    /// Whitespace to trim after the block tag `tag` ends
+    fn block_trim(&self, tag: &str) -> Trim {
+        if tag
+            .strip_suffix(self.indicators.1 .0)
+            .is_some_and(|tag| tag.ends_with(TRIM_MARKER))
+        {
+            Trim::Whitespace
+        } else if self.trim_blocks {
+            Trim::Newline
+        } else {
+            Trim::Nothing
+        }
     }
+}
 
-    #[must_use]
-    pub fn span(&self) -> Span {
-        if self.returned_raw {
-            let mut span = self.state.span();
-            span.start += self.indicators.delimiters().1.len();
+/// Whether `temple_trim_blocks` is enabled in `config`
+#[must_use]
+pub fn trim_blocks(config: &Values) -> bool {
+    config
+        .value_map
+        .get(TRIM_BLOCKS)
+        .and_then(tera::Value::as_bool)
+        .unwrap_or_default()
+}
 
-            span
-        } else {
-            self.state.span()
+/// Returns the start and the end of the tag closing the raw block whose
+/// contents start at `from`
+#[must_use]
+pub fn find_endraw(
+    delimiters: &Delimiters<'_>,
+    contents: &str,
+    from: usize,
+) -> Option<(usize, usize)> {
+    let mut from = from;
+
+    loop {
+        let (start, end, inner) = delimiters.find_placeholder(contents, from)?;
+
+        if inner.eq_ignore_ascii_case(ENDRAW) {
+            return Some((start, end));
         }
+
+        from = start + delimiters.0.len();
     }
+}
 
-    #[must_use]
-    pub fn slice(&self) -> &str {
-        if self.returned_raw {
-            &self.state.slice()[self.indicators.delimiters().1.len()..]
-        } else {
-            self.state.slice()
+/// Returns where the contents of the comment opened by the delimiter ending
+/// at `after_open` start, if the tag there is a comment
+fn comment_start(contents: &str, after_open: usize) -> Option<usize> {
+    let tag = &contents[after_open..];
+    let tag = tag.strip_prefix(TRIM_MARKER).unwrap_or(tag);
+
+    tag.starts_with(COMMENT)
+        .then(|| contents.len() - tag.len() + COMMENT.len_utf8())
+}
+
+/// Returns the end, past the close delimiter, of the comment whose contents
+/// start at `from`
+#[must_use]
+pub fn find_comment_end(delimiters: &Delimiters<'_>, contents: &str, from: usize) -> Option<usize> {
+    let mut search = from;
+
+    loop {
+        let end = delimiters.find_end(contents, search)? + search;
+        let tag = &contents[from..end];
+
+        if tag
+            .strip_suffix(TRIM_MARKER)
+            .unwrap_or(tag)
+            .ends_with(COMMENT)
+        {
+            return Some(end + delimiters.1.len());
         }
-    }
 
-    #[allow(clippy::needless_pass_by_value)]
-    fn error_at(&self, location: Location, msg: impl Into<String>) -> String {
-        format!(
-            "{msg}\n   {arrow}{path}:{line}:{start}\n{empty_pipe}\n{bline} {contents}\n{empty_pipe} {underline}",
-            msg = msg.into(),
-            arrow = "--> ".if_supports_color(owo_colors::Stream::Stdout, |s| s
-                .style(owo_colors::Style::new().bold().blue())),
-            empty_pipe = format!("{: >3} |", "")
-                .if_supports_color(owo_colors::Stream::Stdout, |s| s
-                .style(owo_colors::Style::new().bold().blue())),
-            bline = format!("{line: >3} |", line = location.1)
-                .if_supports_color(owo_colors::Stream::Stdout, |s| s
-                .style(owo_colors::Style::new().bold().blue())),
-            path = self.origin.display(),
-            line = location.1,
-            start = location.0.start,
-            contents = self.get_line(location.1),
-            underline = Underlined(self.get_line(location.1).len())
-        )
+        search = end + 1;
     }
+}
 
-    #[must_use]
-    pub fn get_location(&self, span: Span) -> Location {
-        let mut line = 1;
-        let mut col = 1;
-        for i in 0..span.start {
-            if self
-                .content
-                .chars()
-                .nth(i)
-                .expect("Logos returns valid spans always")
-                == '\n'
-            {
-                line += 1;
-                col = 1;
-            } else {
-                col += 1;
-            }
+/// Returns the start of the first placeholder, comment or raw block in
+/// `contents` that is not closed, if any
+#[must_use]
+pub fn find_unclosed(delimiters: &Delimiters<'_>, contents: &str) -> Option<usize> {
+    let mut from = 0;
+
+    loop {
+        let start = delimiters.find_start(contents, from)? + from;
+        let after_open = start + delimiters.0.len();
+
+        if contents[..start].ends_with('\\') {
+            from = after_open;
+            continue;
         }
 
-        (col..col + (span.end - span.start), line).into()
+        let end = if let Some(comment) = comment_start(contents, after_open) {
+            find_comment_end(delimiters, contents, comment)
+        } else {
+            match delimiters.find_placeholder(contents, start) {
+                Some((_, end, inner)) if inner.eq_ignore_ascii_case(RAW) => {
+                    find_endraw(delimiters, contents, end).map(|(_, end)| end)
+                }
+                Some((_, end, _)) => Some(end),
+                None => None,
+            }
+        };
+
+        match end {
+            Some(end) => from = end,
+            None => return Some(start),
+        }
     }
 }
 
 impl<'i> Iterator for ContentsLexer<'i> {
-    type Item = Result<Type<'i>, anyhow::Error>;
+    type Item = Result<Type<'i>, RenderError>;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.returned_raw = false;
@@ -278,11 +462,12 @@ impl<'i> Iterator for ContentsLexer<'i> {
         }
 
         if self.in_delimiter {
-            let next = self
-                .state
-                .next()
-                .as_mut()
-                .map(|v| v.map_err(|()| anyhow!("")));
+            let next = self.state.next().map(|v| {
+                v.map_err(|()| RenderError::InvalidToken {
+                    location: self.location(self.state.span()),
+                    token: self.state.slice().to_string(),
+                })
+            });
 
             self.in_delimiter = !matches!(
                 next,
@@ -290,38 +475,228 @@ impl<'i> Iterator for ContentsLexer<'i> {
             );
             self.returned_close = !self.in_delimiter;
 
-            next
-        } else if let Some(n) = self.indicators.find_start(self.remainder(), 0) {
-            if self.indicators.find_end(self.remainder(), n).is_none() {
-                let mut span = self.state.span();
+            if self.returned_close && self.state.slice().starts_with(TRIM_MARKER) {
+                self.trim = Trim::Whitespace;
+            }
 
-                span.start += n;
-                span.end += self.indicators.0.len();
+            next
+        } else if let Some(n) = self.find_start() {
+            let rem = self.remainder();
 
-                return Some(Err(anyhow!(self.error_at(
-                    self.get_location(span),
-                    format!("Unclosed delimiter {}", self.indicators.0 .0),
-                ))));
+            // `\{{` outputs the open delimiter as is
+            if rem[..n].ends_with('\\') {
+                self.escaped = true;
+                return Some(Ok(self.raw(n - 1, 1, false)));
             }
 
-            let raw = &self.remainder()[..n];
-            self.in_delimiter = true;
+            let trim_before = rem[n + self.indicators.0.len()..].starts_with(TRIM_MARKER);
+
+            if let Some(comment) = comment_start(rem, n + self.indicators.0.len()) {
+                if n > 0 {
+                    return Some(Ok(self.raw(n, 0, trim_before)));
+                }
+
+                let Some(end) = find_comment_end(&self.indicators, rem, comment) else {
+                    let start = self.state.span().end;
+                    let span = start..start + comment;
+
+                    return Some(Err(RenderError::UnclosedBlock {
+                        location: self.location(span),
+                        expected: format!("{COMMENT}{close}", close = self.indicators.1 .0),
+                    }));
+                };
+
+                // Comments output nothing, a pending trim carries over them
+                let trim = std::mem::take(&mut self.trim).max(self.block_trim(&rem[..end]));
+                let raw = self.raw(0, end, false);
+                self.trim = trim;
 
-            if self.returned_close {
-                self.returned_raw = true;
-                self.returned_close = false;
+                return Some(Ok(raw));
             }
 
-            self.bump(raw.len());
+            let Some((_, end, inner)) = self.indicators.find_placeholder(rem, n) else {
+                let start = self.state.span().end + n;
+                let span = start..start + self.indicators.0.len();
+
+                return Some(Err(RenderError::UnclosedDelimiter {
+                    location: self.location(span),
+                    delimiter: self.indicators.0 .0.to_string(),
+                }));
+            };
+
+            if inner.eq_ignore_ascii_case(RAW) {
+                if n > 0 {
+                    return Some(Ok(self.raw(n, 0, trim_before)));
+                }
+
+                let Some((endraw, endraw_end)) = find_endraw(&self.indicators, rem, end) else {
+                    let start = self.state.span().end;
+                    let span = start..start + end;
+
+                    return Some(Err(RenderError::UnclosedBlock {
+                        location: self.location(span),
+                        expected: format!(
+                            "{open} {ENDRAW} {close}",
+                            open = self.indicators.0 .0,
+                            close = self.indicators.1 .0
+                        ),
+                    }));
+                };
+
+                let trim_end = rem[endraw + self.indicators.0.len()..].starts_with(TRIM_MARKER);
+
+                self.bump(end);
+                self.trim = self.block_trim(&rem[..end]);
+                let raw = self.raw(endraw - end, endraw_end - endraw, trim_end);
+                self.trim = self.block_trim(&rem[..endraw_end]);
+
+                return Some(Ok(raw));
+            }
+
+            self.in_delimiter = true;
 
-            Some(Ok(Type::Raw(raw)))
+            Some(Ok(self.raw(n, 0, trim_before)))
         } else {
-            self.returned_raw = true;
+            let len = self.remainder().len();
 
-            let rem = self.remainder();
-            self.bump(rem.len());
+            Some(Ok(self.raw(len, 0, false)))
+        }
+    }
+}
 
-            Some(Ok(Type::Raw(rem)))
+#[cfg(test)]
+mod tests {
+    use super::{ContentsLexer, DelimiterType, Type};
+    use crate::{delimit::Delimiters, error::RenderError, values::Values};
+    use std::path::Path;
+
+    const CONFIG: &str =
+        r#"temple_delimiters: { open: String, close: String } = { open: "{{", close: "}}" }"#;
+
+    #[test]
+    fn escapes_and_raw_blocks() {
+        use Type::*;
+
+        let config = Values::from_str(CONFIG, Path::new("config.tpl")).expect("Valid config");
+        let inp = r"a \{{ b }} {{ raw }}{{ c }}{{ endraw }}{{ d }}";
+
+        let tokens = ContentsLexer::new(inp, Path::new("test"), &config)
+            .expect("Delimiters are set")
+            .map(std::result::Result::unwrap)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens.as_slice(),
+            &[
+                Raw("a "),
+                Raw("{{ b }} "),
+                Raw("{{ c }}"),
+                Raw(""),
+                PotentialDelim(DelimiterType::DelimitOpen),
+                Ident("d"),
+                PotentialDelim(DelimiterType::DelimitClose),
+            ]
+        );
+    }
+
+    #[test]
+    fn delimiters_per_lexer() {
+        use Type::*;
+
+        let config = Values::from_str(CONFIG, Path::new("config.tpl")).expect("Valid config");
+        let inp = "{{ a }} <% b %>";
+
+        let mut curly = ContentsLexer::new(inp, Path::new("curly"), &config)
+            .expect("Delimiters are set")
+            .map(std::result::Result::unwrap);
+        let mut percent = ContentsLexer::with_delimiters(
+            inp,
+            Path::new("percent"),
+            Delimiters("<%".into(), "%>".into()),
+        )
+        .map(std::result::Result::unwrap);
+
+        // Interleaved, each lexer keeps recognising its own delimiters
+        let mut tokens = Vec::new();
+        loop {
+            match (curly.next(), percent.next()) {
+                (None, None) => break,
+                (a, b) => tokens.push((a, b)),
+            }
         }
+
+        let (curly, percent): (Vec<_>, Vec<_>) = tokens.into_iter().unzip();
+
+        assert_eq!(
+            curly.into_iter().flatten().collect::<Vec<_>>(),
+            &[
+                Raw(""),
+                PotentialDelim(DelimiterType::DelimitOpen),
+                Ident("a"),
+                PotentialDelim(DelimiterType::DelimitClose),
+                Raw(" <% b %>"),
+            ]
+        );
+        assert_eq!(
+            percent.into_iter().flatten().collect::<Vec<_>>(),
+            &[
+                Raw("{{ a }} "),
+                PotentialDelim(DelimiterType::DelimitOpen),
+                Ident("b"),
+                PotentialDelim(DelimiterType::DelimitClose),
+            ]
+        );
+    }
+
+    #[test]
+    fn trim_markers() {
+        use Type::*;
+
+        let config = Values::from_str(CONFIG, Path::new("config.tpl")).expect("Valid config");
+        let inp = "a \n{{- b -}}\n c {{ raw -}}\n d\n{{- endraw }}";
+
+        let tokens = ContentsLexer::new(inp, Path::new("test"), &config)
+            .expect("Delimiters are set")
+            .map(std::result::Result::unwrap)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens.as_slice(),
+            &[
+                Raw("a"),
+                PotentialDelim(DelimiterType::DelimitOpen),
+                Ident("b"),
+                PotentialDelim(DelimiterType::DelimitClose),
+                Raw("c "),
+                Raw("d"),
+            ]
+        );
+    }
+
+    #[test]
+    fn comments() {
+        use Type::*;
+
+        let config = Values::from_str(CONFIG, Path::new("config.tpl")).expect("Valid config");
+        let inp = "a {{# {{ b }}\n c #}} d\n{{-# e #-}}\n f{{##}}";
+
+        let tokens = ContentsLexer::new(inp, Path::new("test"), &config)
+            .expect("Delimiters are set")
+            .map(std::result::Result::unwrap)
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            tokens.as_slice(),
+            &[Raw("a "), Raw(""), Raw(" d"), Raw(""), Raw("f"), Raw("")]
+        );
+
+        let mut unclosed = ContentsLexer::new("a {{# b }}", Path::new("test"), &config)
+            .expect("Delimiters are set");
+
+        let Some(Err(RenderError::UnclosedBlock { location, expected })) = unclosed.nth(1) else {
+            panic!("Expected an unclosed comment");
+        };
+        assert_eq!((location.line, location.columns), (1, 3..6));
+        assert_eq!(expected, "#}}");
     }
 }
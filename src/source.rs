@@ -0,0 +1,426 @@
+use anyhow::{anyhow, ensure, Context};
+use std::{path::Path, process::Command};
+use tera::Value;
+
+use crate::values::{Type, Values};
+
+/// Name of the file, next to `config.tpl`, that records where a template
+/// was installed from
+pub const SOURCE_FILE: &str = "source.tpl";
+
+/// Where an installed template comes from
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Source {
+    pub url: String,
+    pub rev: Option<String>,
+    pub commit: String,
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.url)?;
+
+        if let Some(ref rev) = self.rev {
+            write!(f, " @ {rev}")?;
+        }
+
+        write!(f, " ({})", self.commit.get(..7).unwrap_or(&self.commit))
+    }
+}
+
+impl Source {
+    /// Clones the git repository at `url` into `into` and checks out `rev`
+    /// if given, returning the resulting [`Source`].
+    ///
+    /// Any url `git clone` understands works, including `file://` urls
+    /// and paths to local (bare) repositories.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Err`] if `git` is not available, any of the `git`
+    /// commands fails or `url` or `rev` can not be recorded
+    pub fn clone_into(url: &str, rev: Option<&str>, into: &Path) -> anyhow::Result<Self> {
+        ensure_recordable("url", url)?;
+        rev.map(|rev| ensure_recordable("revision", rev))
+            .transpose()?;
+
+        crate::trace!("Cloning {url} into {}", into.display());
+
+        git(None, &["clone", "--quiet", "--", url])
+            .arg(into)
+            .status_ok()
+            .with_context(|| format!("Failed cloning {url}"))?;
+
+        if let Some(rev) = rev {
+            crate::trace!("Checking out {rev}");
+
+            git(Some(into), &["checkout", "--quiet", rev, "--"])
+                .status_ok()
+                .with_context(|| format!("Failed checking out revision '{rev}'"))?;
+        }
+
+        Ok(Self {
+            url: url.to_owned(),
+            rev: rev.map(ToOwned::to_owned),
+            commit: head_commit(into)?,
+        })
+    }
+
+    /// Reads the source metadata stored inside `template`, if any
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Err`] if the metadata file exists but can not be read or
+    /// is missing any of the required keys
+    pub fn read(template: &Path) -> anyhow::Result<Option<Self>> {
+        let path = template.join(SOURCE_FILE);
+
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|err| anyhow!("Failed reading {}: {err}", path.display()))?;
+        let values = Values::from_str(&contents, &path)?;
+
+        let get = |key: &str| {
+            values
+                .value_map
+                .get(key)
+                .and_then(|v| v.as_str())
+                .map(ToOwned::to_owned)
+        };
+
+        Ok(Some(Self {
+            url: get("url").ok_or(anyhow!("Missing 'url' in {}", path.display()))?,
+            rev: get("rev"),
+            commit: get("commit").ok_or(anyhow!("Missing 'commit' in {}", path.display()))?,
+        }))
+    }
+
+    /// Writes the source metadata to [`SOURCE_FILE`] inside `template`
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Err`] if any of the fields can not be written in the
+    /// config syntax or any IO error occurs
+    pub fn write(&self, template: &Path) -> anyhow::Result<()> {
+        let mut values = Values::default();
+
+        for (key, value) in [
+            ("url", Some(&self.url)),
+            ("rev", self.rev.as_ref()),
+            ("commit", Some(&self.commit)),
+        ] {
+            if let Some(value) = value {
+                values.type_map.insert(key.to_owned(), Type::String);
+                values
+                    .value_map
+                    .insert(key.to_owned(), Value::String(value.clone()));
+            }
+        }
+
+        let contents = values
+            .to_config()
+            .context("Failed writing source metadata")?;
+
+        std::fs::write(template.join(SOURCE_FILE), contents)
+            .map_err(|err| anyhow!("Failed writing source metadata: {err}"))
+    }
+
+    /// Fetches the recorded source and resolves the commit `rev` points to.
+    /// When no `rev` is given the recorded one is used, falling back to the
+    /// default branch of the source.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Err`] if fetching fails, the revision does not exist or
+    /// can not be recorded
+    pub fn fetch(&self, template: &Path, rev: Option<&str>) -> anyhow::Result<String> {
+        rev.map(|rev| ensure_recordable("revision", rev))
+            .transpose()?;
+
+        crate::trace!("Fetching {}", self.url);
+
+        git(
+            Some(template),
+            &[
+                "fetch",
+                "--quiet",
+                "--tags",
+                "--force",
+                &self.url,
+                "+refs/heads/*:refs/remotes/origin/*",
+            ],
+        )
+        .status_ok()
+        .with_context(|| format!("Failed fetching {}", self.url))?;
+
+        let rev = rev.or(self.rev.as_deref());
+        let candidates = match rev {
+            Some(rev) => [format!("origin/{rev}"), rev.to_owned()],
+            None => ["origin/HEAD".to_owned(), "FETCH_HEAD".to_owned()],
+        };
+
+        candidates
+            .iter()
+            .find_map(|candidate| {
+                git_output(
+                    Some(template),
+                    &[
+                        "rev-parse",
+                        "--verify",
+                        "--quiet",
+                        &format!("{candidate}^{{commit}}"),
+                    ],
+                )
+                .ok()
+            })
+            .ok_or(anyhow!(
+                "Revision '{}' does not exist in {}",
+                rev.unwrap_or("HEAD"),
+                self.url
+            ))
+    }
+}
+
+/// Returns the default template name for a repository url. E.g. `rust-cli`
+/// for `https://host/user/rust-cli.git`
+#[must_use]
+pub fn name_from_url(url: &str) -> Option<&str> {
+    url.trim_end_matches(['/', '\\'])
+        .rsplit(['/', '\\', ':'])
+        .next()
+        .map(|name| name.trim_end_matches(".git"))
+        .filter(|name| !name.is_empty())
+}
+
+/// Returns the commit `HEAD` points to in the repository at `repo`
+///
+/// # Errors
+///
+/// Returns an [`Err`] if the `git` command fails
+pub fn head_commit(repo: &Path) -> anyhow::Result<String> {
+    git_output(Some(repo), &["rev-parse", "HEAD"])
+}
+
+/// Returns the files of the template that differ from the checked out commit,
+/// ignoring the source metadata file
+///
+/// # Errors
+///
+/// Returns an [`Err`] if the `git` command fails
+pub fn local_changes(template: &Path) -> anyhow::Result<Vec<String>> {
+    let exclude = format!(":!{SOURCE_FILE}");
+
+    Ok(git_output(
+        Some(template),
+        &["status", "--porcelain", "--", ".", &exclude],
+    )?
+    .lines()
+    .map(|line| line.trim().to_owned())
+    .collect())
+}
+
+/// Returns the files that changed between commits `from` and `to`, one per
+/// line prefixed with the kind of change (`A`, `M`, `D`, ...)
+///
+/// # Errors
+///
+/// Returns an [`Err`] if the `git` command fails
+pub fn changed_files(template: &Path, from: &str, to: &str) -> anyhow::Result<Vec<String>> {
+    Ok(
+        git_output(Some(template), &["diff", "--name-status", from, to])?
+            .lines()
+            .map(|line| line.replace('\t', " "))
+            .collect(),
+    )
+}
+
+/// Checks out `commit` in `template`. With `force` any local changes and
+/// untracked files are discarded
+///
+/// # Errors
+///
+/// Returns an [`Err`] if the `git` command fails
+pub fn checkout(template: &Path, commit: &str, force: bool) -> anyhow::Result<()> {
+    let mut cmd = git(Some(template), &["checkout", "--quiet", "--detach"]);
+
+    if force {
+        cmd.arg("--force");
+    }
+
+    cmd.arg(commit)
+        .status_ok()
+        .with_context(|| format!("Failed checking out {commit}"))?;
+
+    if force {
+        git(
+            Some(template),
+            &["clean", "--quiet", "-d", "--force", "--exclude"],
+        )
+        .arg(SOURCE_FILE)
+        .status_ok()
+        .context("Failed removing untracked files")?;
+    }
+
+    Ok(())
+}
+
+/// Fails if `value` can not be written in the source metadata, as strings in
+/// the config syntax can not contain both kinds of quotes
+fn ensure_recordable(what: &str, value: &str) -> anyhow::Result<()> {
+    ensure!(
+        !(value.contains('"') && value.contains('\'')),
+        "The {what} {value} contains both `\"` and `'`, which temple can not record"
+    );
+
+    Ok(())
+}
+
+fn git(at: Option<&Path>, args: &[&str]) -> Command {
+    let mut cmd = Command::new("git");
+
+    if let Some(at) = at {
+        cmd.arg("-C").arg(at);
+    }
+
+    cmd.args(args);
+    cmd
+}
+
+fn git_output(at: Option<&Path>, args: &[&str]) -> anyhow::Result<String> {
+    let out = git(at, args)
+        .output()
+        .map_err(|err| anyhow!("Failed running git: {err}"))?;
+
+    ensure!(
+        out.status.success(),
+        "git {} failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&out.stderr).trim()
+    );
+
+    Ok(String::from_utf8_lossy(&out.stdout).trim_end().to_owned())
+}
+
+trait StatusOk {
+    fn status_ok(&mut self) -> anyhow::Result<()>;
+}
+
+impl StatusOk for Command {
+    fn status_ok(&mut self) -> anyhow::Result<()> {
+        let status = self
+            .status()
+            .map_err(|err| anyhow!("Failed running git: {err}"))?;
+
+        ensure!(status.success(), "git exited with {status}");
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{git, git_output, head_commit, Source, StatusOk};
+    use std::path::Path;
+
+    /// Creates a bare repository at `dir/remote.git` with a commit for each
+    /// of `files`, tagging the first one as `v1`, and returns its commits
+    fn remote(dir: &Path, files: &[(&str, &str)]) -> Vec<String> {
+        let work = dir.join("work");
+        let run = |at: &Path, args: &[&str]| {
+            git(
+                Some(at),
+                &[
+                    "-c",
+                    "user.name=temple",
+                    "-c",
+                    "user.email=temple@localhost",
+                ],
+            )
+            .args(args)
+            .status_ok()
+            .expect("Git command failed");
+        };
+
+        run(
+            dir,
+            &["init", "--quiet", "--bare", "-b", "main", "remote.git"],
+        );
+        run(dir, &["init", "--quiet", "-b", "main", "work"]);
+
+        let commits = files
+            .iter()
+            .map(|(name, contents)| {
+                std::fs::write(work.join(name), contents).expect("Failed writing file");
+                run(&work, &["add", "."]);
+                run(&work, &["commit", "--quiet", "--no-gpg-sign", "-m", name]);
+                head_commit(&work).expect("Missing commit")
+            })
+            .collect::<Vec<_>>();
+
+        run(&work, &["tag", "v1", &commits[0]]);
+        run(
+            &work,
+            &["push", "--quiet", "--tags", "../remote.git", "main"],
+        );
+
+        commits
+    }
+
+    #[test]
+    fn clone_from_bare_repository() {
+        let dir = tempfile::tempdir().expect("Failed creating temp dir");
+        let commits = remote(dir.path(), &[("a", "1"), ("b", "2")]);
+        let url = dir.path().join("remote.git").display().to_string();
+
+        let head = dir.path().join("head");
+        let source = Source::clone_into(&url, None, &head).expect("Failed cloning");
+        assert_eq!(source.commit, commits[1]);
+        assert_eq!(source.rev, None);
+        assert!(head.join("b").is_file());
+
+        let pinned = dir.path().join("pinned");
+        let source = Source::clone_into(&url, Some("v1"), &pinned).expect("Failed cloning");
+        assert_eq!(source.commit, commits[0]);
+        assert_eq!(source.rev.as_deref(), Some("v1"));
+        assert!(!pinned.join("b").exists());
+
+        assert!(Source::clone_into(&url, Some("v2"), &dir.path().join("missing")).is_err());
+        assert_eq!(
+            git_output(Some(&pinned), &["remote", "get-url", "origin"]).expect("Missing remote"),
+            url
+        );
+    }
+
+    #[test]
+    fn write_and_read() {
+        let dir = tempfile::tempdir().expect("Failed creating temp dir");
+
+        assert_eq!(Source::read(dir.path()).expect("Failed reading"), None);
+
+        for rev in [None, Some("feature/\"quoted\"".to_owned())] {
+            let source = Source {
+                url: "/path/to/a \"repo\"".to_owned(),
+                rev,
+                commit: "0123456789abcdef".to_owned(),
+            };
+
+            source.write(dir.path()).expect("Failed writing");
+            assert_eq!(
+                Source::read(dir.path()).expect("Failed reading"),
+                Some(source)
+            );
+        }
+
+        // Sources that can not be written are refused before cloning
+        let into = dir.path().join("clone");
+        let err = Source::clone_into("/it's a \"repo\"", None, &into).expect_err("Both quotes");
+        assert!(err.to_string().contains("can not record"), "{err}");
+
+        let err = Source::clone_into("/repo", Some("it's \"v1\""), &into).expect_err("Both quotes");
+        assert!(err.to_string().contains("can not record"), "{err}");
+        assert!(!into.exists());
+    }
+}
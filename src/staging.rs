@@ -0,0 +1,305 @@
+use anyhow::{anyhow, bail};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Rendered files waiting in a temporary directory until every file of the
+/// template has been rendered.
+///
+/// Nothing outside of the staging directory is touched until [`Staging::commit`],
+/// which moves the files into place and undoes every move if any of them fails.
+/// The staging directory is removed once the value is dropped.
+pub struct Staging {
+    dir: PathBuf,
+    files: Vec<(PathBuf, PathBuf)>,
+}
+
+/// A change made while committing, in the order it was made
+enum Change {
+    CreatedDir(PathBuf),
+    Created(PathBuf),
+    Replaced { target: PathBuf, backup: PathBuf },
+}
+
+impl Staging {
+    /// Creates an empty staging directory for the project `name` inside the
+    /// temporary directory of the system. Every staging gets a new directory,
+    /// existing ones are never reused nor removed
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Err`] if the directory can not be created
+    pub fn new(name: &str) -> anyhow::Result<Self> {
+        static CREATED: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = loop {
+            let dir = std::env::temp_dir().join(format!(
+                "temple-{name}-{pid}-{n}",
+                name = name.replace(['/', '\\'], "_"),
+                pid = std::process::id(),
+                n = CREATED.fetch_add(1, Ordering::Relaxed)
+            ));
+
+            match std::fs::create_dir(&dir) {
+                Ok(()) => break dir,
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {}
+                Err(err) => bail!("Failed creating staging dir {}: {err}", dir.display()),
+            }
+        };
+
+        std::fs::create_dir(dir.join("files"))
+            .map_err(|err| anyhow!("Failed creating staging dir {}: {err}", dir.display()))?;
+
+        crate::trace!("Staging rendered files at {}", dir.display());
+
+        Ok(Self {
+            dir,
+            files: Vec::new(),
+        })
+    }
+
+    /// Creates the file where the `i`-th file of the template is staged,
+    /// returning its path to [`Staging::add`] it once written
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Err`] if the file can not be created
+    pub fn create(&self, i: usize) -> anyhow::Result<(PathBuf, File)> {
+        let staged = self.dir.join("files").join(i.to_string());
+
+        let file = File::create(&staged)
+            .map_err(|err| anyhow!("Failed creating staged file {}: {err}", staged.display()))?;
+
+        Ok((staged, file))
+    }
+
+    /// Stages the file at `staged`, made with [`Staging::create`], to be moved
+    /// to `target`
+    pub fn add(&mut self, staged: PathBuf, target: &Path) {
+        self.files.push((staged, target.to_path_buf()));
+    }
+
+    /// Moves every staged file to its target, creating the missing parent
+    /// directories. Existing targets are backed up first so that, if any move
+    /// fails, the created files and directories are removed and the replaced
+    /// files are restored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Err`] if any file can not be moved into place, after
+    /// rolling back the previous moves
+    pub fn commit(self) -> anyhow::Result<()> {
+        let mut changes = Vec::new();
+
+        for (i, (staged, target)) in self.files.iter().enumerate() {
+            if let Err(err) = self.place(i, staged, target, &mut changes) {
+                return match rollback(changes) {
+                    Ok(()) => Err(anyhow!(
+                        "{err}\nThe changes were rolled back, nothing was modified"
+                    )),
+                    Err(rollback_err) => Err(anyhow!(
+                        "{err}\nFailed rolling back the changes, the target may be left half rendered: {rollback_err}"
+                    )),
+                };
+            }
+        }
+
+        Ok(())
+    }
+
+    fn place(
+        &self,
+        i: usize,
+        staged: &Path,
+        target: &Path,
+        changes: &mut Vec<Change>,
+    ) -> anyhow::Result<()> {
+        if let Some(parent) = target.parent() {
+            let missing = parent
+                .ancestors()
+                .take_while(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+                .collect::<Vec<_>>();
+
+            for dir in missing.into_iter().rev() {
+                std::fs::create_dir(dir)
+                    .map_err(|err| anyhow!("Failed creating dir {}: {err}", dir.display()))?;
+                changes.push(Change::CreatedDir(dir.to_path_buf()));
+            }
+        }
+
+        if target.is_dir() {
+            bail!(
+                "Failed writing {}: a directory with the same name exists",
+                target.display()
+            );
+        }
+
+        if target.exists() {
+            let backup = self.dir.join(format!("backup-{i}"));
+
+            move_file(target, &backup)
+                .map_err(|err| anyhow!("Failed backing up {}: {err}", target.display()))?;
+            changes.push(Change::Replaced {
+                target: target.to_path_buf(),
+                backup,
+            });
+        } else {
+            changes.push(Change::Created(target.to_path_buf()));
+        }
+
+        move_file(staged, target)
+            .map_err(|err| anyhow!("Failed writing {}: {err}", target.display()))
+    }
+}
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_dir_all(&self.dir) {
+            crate::warn!("Failed removing staging dir {}: {err}", self.dir.display());
+        }
+    }
+}
+
+/// Undoes `changes` in reverse order, trying every change even if some fail
+fn rollback(changes: Vec<Change>) -> anyhow::Result<()> {
+    let mut failed = Vec::new();
+
+    for change in changes.into_iter().rev() {
+        let undone = match change {
+            Change::CreatedDir(ref dir) => std::fs::remove_dir(dir),
+            Change::Created(ref file) if file.exists() => std::fs::remove_file(file),
+            Change::Created(_) => Ok(()),
+            Change::Replaced {
+                ref target,
+                ref backup,
+            } => {
+                if target.exists() {
+                    _ = std::fs::remove_file(target);
+                }
+
+                move_file(backup, target)
+            }
+        };
+
+        if let Err(err) = undone {
+            failed.push(err.to_string());
+        }
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        bail!(failed.join(", "))
+    }
+}
+
+/// Renames `from` to `to`, copying it instead when both are on different
+/// file systems
+fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    std::fs::copy(from, to)?;
+    std::fs::remove_file(from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{move_file, Staging};
+    use std::{io::Write, path::Path};
+
+    fn stage(staging: &mut Staging, i: usize, contents: &str, target: &Path) {
+        let (staged, mut file) = staging.create(i).expect("Failed creating staged file");
+        file.write_all(contents.as_bytes())
+            .expect("Failed writing staged file");
+        staging.add(staged, target);
+    }
+
+    fn read(path: &Path) -> String {
+        std::fs::read_to_string(path).expect("Failed reading file")
+    }
+
+    #[test]
+    fn commit() {
+        let dir = tempfile::tempdir().expect("Failed creating temp dir");
+        let target = dir.path();
+        std::fs::write(target.join("a"), "old").expect("Failed writing file");
+
+        let mut staging = Staging::new("staging-commit").expect("Failed staging");
+        let staging_dir = staging.dir.clone();
+        stage(&mut staging, 0, "new", &target.join("a"));
+        stage(&mut staging, 1, "nested", &target.join("b/c/d"));
+
+        staging.commit().expect("Failed committing");
+
+        assert_eq!(read(&target.join("a")), "new");
+        assert_eq!(read(&target.join("b/c/d")), "nested");
+        assert!(!staging_dir.exists());
+    }
+
+    #[test]
+    fn unique_dirs() {
+        let first = Staging::new("staging-unique").expect("Failed staging");
+        let second = Staging::new("staging-unique").expect("Failed staging");
+
+        assert_ne!(first.dir, second.dir);
+        assert!(first.dir.join("files").is_dir());
+        assert!(second.dir.join("files").is_dir());
+
+        drop(first);
+        assert!(second.dir.join("files").is_dir());
+    }
+
+    #[test]
+    fn rollback() {
+        let dir = tempfile::tempdir().expect("Failed creating temp dir");
+        let target = dir.path();
+        std::fs::write(target.join("a"), "old").expect("Failed writing file");
+        std::fs::create_dir(target.join("dir")).expect("Failed creating dir");
+
+        let mut staging = Staging::new("staging-rollback").expect("Failed staging");
+        stage(&mut staging, 0, "new", &target.join("a"));
+        stage(&mut staging, 1, "created", &target.join("b/c"));
+        // A directory can not be replaced by a file, failing the commit
+        stage(&mut staging, 2, "file", &target.join("dir"));
+
+        let err = staging.commit().expect_err("The commit fails");
+        assert!(err.to_string().contains("rolled back"));
+
+        assert_eq!(read(&target.join("a")), "old");
+        assert!(!target.join("b").exists());
+        assert!(target.join("dir").is_dir());
+
+        let mut left = std::fs::read_dir(target)
+            .expect("Failed reading dir")
+            .map(|entry| entry.expect("Valid entry").file_name())
+            .collect::<Vec<_>>();
+        left.sort();
+        assert_eq!(left, ["a", "dir"]);
+    }
+
+    #[test]
+    fn move_across_file_systems() {
+        // /dev/shm is usually a tmpfs, where renaming from the temporary
+        // directory fails and the file is copied instead
+        let other = Path::new("/dev/shm");
+        if !other.is_dir() {
+            return;
+        }
+
+        let from = tempfile::tempdir().expect("Failed creating temp dir");
+        let to = tempfile::tempdir_in(other).expect("Failed creating temp dir");
+
+        std::fs::write(from.path().join("a"), "moved").expect("Failed writing file");
+        move_file(&from.path().join("a"), &to.path().join("a")).expect("Failed moving");
+
+        assert!(!from.path().join("a").exists());
+        assert_eq!(read(&to.path().join("a")), "moved");
+
+        move_file(&to.path().join("a"), &from.path().join("b")).expect("Failed moving");
+        assert_eq!(read(&from.path().join("b")), "moved");
+    }
+}
@@ -0,0 +1,173 @@
+use std::path::Path;
+use tera::Value;
+
+use crate::error::{ConfigError, Location};
+
+use super::{Type, Values};
+
+/// Data format values can be loaded from besides the config syntax
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    /// Returns the format of the file at `path` from its extension, [`None`]
+    /// for files in the config syntax
+    #[must_use]
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "json" => Some(Format::Json),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "toml" => Some(Format::Toml),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Format::Json => "JSON",
+            Format::Yaml => "YAML",
+            Format::Toml => "TOML",
+        })
+    }
+}
+
+/// Returns the location of the 1-based `line` and byte `column` of `s`
+fn location_at(path: &Path, s: &str, line: usize, column: usize) -> Location {
+    let line_start = s
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum::<usize>();
+    let start = (line_start + column.saturating_sub(1)).min(s.len());
+
+    Location::new(path, s, start..start, 1)
+}
+
+impl Values {
+    /// Parses `s`, the contents of the file at `path` written in `format`.
+    /// The document must be an object, its keys are the keys of the values
+    /// and their types are inferred with [`Type::from_value`]
+    pub fn from_data(s: &str, path: &Path, format: Format) -> Result<Self, ConfigError> {
+        let invalid = |location, message: String| ConfigError::Format {
+            format,
+            location,
+            message,
+        };
+
+        let value = match format {
+            Format::Json => serde_json::from_str::<Value>(s).map_err(|err| {
+                let location = location_at(path, s, err.line(), err.column());
+                invalid(Some(location), err.to_string())
+            })?,
+            Format::Yaml => serde_yaml::from_str::<Value>(s).map_err(|err| {
+                let location = err
+                    .location()
+                    .map(|at| Location::new(path, s, at.index()..at.index(), 1));
+                invalid(location, err.to_string())
+            })?,
+            Format::Toml => toml::from_str::<Value>(s).map_err(|err| {
+                let location = err.span().map(|span| Location::new(path, s, span, 1));
+                invalid(location, err.message().to_owned())
+            })?,
+        };
+
+        let Value::Object(object) = value else {
+            return Err(invalid(
+                None,
+                format!("{} must contain an object of keys", path.display()),
+            ));
+        };
+
+        let mut values = Values::default();
+
+        for (key, value) in object {
+            values
+                .type_map
+                .insert(key.clone(), Type::from_value(&value, &Type::Any));
+            values.value_map.insert(key, value);
+        }
+
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Format;
+    use crate::{
+        error::ConfigError,
+        values::{Type, Values},
+    };
+    use std::path::Path;
+    use tera::Value;
+
+    #[test]
+    fn from_data() {
+        let documents = [
+            (
+                "v.json",
+                r#"{ "name": "temple", "count": 2, "tags": ["a", "b"], "author": { "age": 30 } }"#,
+            ),
+            (
+                "v.yaml",
+                "name: temple\ncount: 2\ntags: [a, b]\nauthor:\n  age: 30\n",
+            ),
+            (
+                "v.toml",
+                "name = \"temple\"\ncount = 2\ntags = [\"a\", \"b\"]\n[author]\nage = 30\n",
+            ),
+        ];
+
+        for (path, document) in documents {
+            let path = Path::new(path);
+            let format = Format::from_path(path).expect("Known extension");
+            let values = Values::from_data(document, path, format).expect("Valid document");
+
+            assert_eq!(values.value_map["name"], Value::from("temple"), "{path:?}");
+            assert_eq!(values.value_map["count"], Value::from(2), "{path:?}");
+            assert_eq!(values.type_map["count"], Type::Number, "{path:?}");
+            assert_eq!(
+                values.type_map["tags"],
+                Type::Array(Box::new(Type::String)),
+                "{path:?}"
+            );
+            assert_eq!(
+                values.type_map["author"],
+                Type::Object([("age".to_string(), Type::Number)].into()),
+                "{path:?}"
+            );
+        }
+
+        assert_eq!(Format::from_path(Path::new("v.YML")), Some(Format::Yaml));
+        assert_eq!(Format::from_path(Path::new("config.tpl")), None);
+    }
+
+    #[test]
+    fn from_data_errors() {
+        let err = Values::from_data("{\n  \"a\": 1,\n  ]\n}", Path::new("v.json"), Format::Json)
+            .expect_err("Invalid JSON");
+        let location = err.location().expect("JSON errors have a location");
+        assert_eq!((location.line, location.columns.start), (3, 3));
+
+        let err = Values::from_data("a = 1\nb = \n", Path::new("v.toml"), Format::Toml)
+            .expect_err("Invalid TOML");
+        assert_eq!(err.location().map(|location| location.line), Some(2));
+
+        let err = Values::from_data("- a\n- b\n", Path::new("v.yaml"), Format::Yaml)
+            .expect_err("Not an object");
+        assert!(matches!(
+            err,
+            ConfigError::Format {
+                format: Format::Yaml,
+                location: None,
+                ..
+            }
+        ));
+    }
+}
@@ -1,27 +1,36 @@
+mod data;
 mod parser;
+mod syntax;
 mod token;
+mod writer;
+
+pub use data::Format;
+pub use syntax::format;
 
-use anyhow::{anyhow, ensure};
 use std::{
     collections::{hash_map::Entry, HashMap},
-    ops::{Deref, DerefMut, Not as _},
+    ops::{Deref, DerefMut},
     path::Path,
 };
 use tera::Value;
 use token::{Logos, Variant};
 
-use crate::{values::token::Tokens, warn};
+use crate::{
+    error::{ConfigError, Location},
+    values::token::Tokens,
+    warn,
+};
 
-#[derive(Debug, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct Values {
     pub value_map: ValueMap,
     pub type_map: TypeMap,
 }
 
-#[derive(Debug, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct ValueMap(HashMap<String, Value>);
 
-#[derive(Debug, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub struct TypeMap(HashMap<String, Type>);
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -59,6 +68,10 @@ impl std::fmt::Display for Type {
             Type::Object(fields) => {
                 write!(f, "Object {{ ")?;
 
+                // Sorted for the output to be the same every time
+                let mut fields = fields.iter().collect::<Vec<_>>();
+                fields.sort_by_key(|(k, _)| *k);
+
                 for (i, (k, v)) in fields.iter().enumerate() {
                     write!(f, "{k}: {v}")?;
                     if i != fields.len() - 1 {
@@ -181,8 +194,32 @@ impl Values {
         self
     }
 
-    pub fn verify_types(&self) -> anyhow::Result<()> {
-        let mut res = Ok(());
+    /// Returns the declared keys as a JSON object sorted by key, with the
+    /// type and the value of each. Keys without a value have a null value
+    #[must_use]
+    pub fn to_json(&self) -> serde_json::Map<String, Value> {
+        let mut keys = self.type_map.keys().collect::<Vec<_>>();
+        keys.sort();
+
+        keys.into_iter()
+            .map(|key| {
+                let value = self.value_map.get(key).cloned().unwrap_or_default();
+
+                (
+                    key.clone(),
+                    serde_json::json!({
+                        "type": self.type_map[key].to_string(),
+                        "value": value,
+                    }),
+                )
+            })
+            .collect()
+    }
+
+    /// Checks every value against its declared type, returning all the
+    /// mismatches found
+    pub fn verify_types(&self) -> Result<(), Vec<ConfigError>> {
+        let mut errors = Vec::new();
 
         for (k, v) in self.value_map.iter() {
             let decl_type = self.type_map.get(k).expect("Missing value");
@@ -192,16 +229,20 @@ impl Values {
             crate::trace!("Real type of '{k}' is {val_type}");
 
             if !decl_type.is_equivalent_or_empty(&val_type) {
-                crate::error!(
-                    "The value of '{k}' does not match with the declared type\n    Value: {v}\n    Decl type: {decl_type}\n    Real type: {val_type}",
-                    v = format!("{v:#}").replace('\n', "\n    ")
-                );
-
-                res = Err(anyhow!("Invalid configuration values/types"));
+                errors.push(ConfigError::TypeMismatch {
+                    key: k.clone(),
+                    value: v.clone(),
+                    declared: Box::new(decl_type.clone()),
+                    found: Box::new(val_type),
+                });
             }
         }
 
-        res
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
     }
 }
 
@@ -234,52 +275,71 @@ impl Deref for TypeMap {
 }
 
 impl Values {
-    pub fn parse_value(s: &str, source: &str) -> std::result::Result<Value, anyhow::Error> {
+    pub fn parse_value(s: &str, source: &str) -> Result<Value, ConfigError> {
         let mut tokens = get_tokens(s, source, false)?;
         let value = parser::try_value_from(&mut tokens)?;
 
-        ensure! {
-            tokens.is_empty(),
-            anyhow!(tokens.error_current_span(format!("Invalid syntax on config, expected to finish parsing everything but remains: {:?}", tokens.tokens())))
-        };
+        if !tokens.is_empty() {
+            return Err(tokens.expected("the end of the value"));
+        }
 
         Ok(value)
     }
 
-    pub fn from_str(s: &str, path: &Path) -> std::result::Result<Self, anyhow::Error> {
-        let mut tokens = get_tokens(s, &format!("{}", path.display()), true)?;
+    /// Parses the config `s` read from `path`, ignoring the problems that
+    /// do not stop parsing, see [`Values::from_str_with_warnings`]
+    pub fn from_str(s: &str, path: &Path) -> Result<Self, ConfigError> {
+        Self::from_str_with_warnings(s, path).map(|(values, _)| values)
+    }
+
+    /// Parses the config `s` read from `path`, also returning the problems
+    /// that did not stop parsing as warnings: invalid tokens, which are
+    /// skipped, keys defined twice and keys without a value nor a type
+    pub fn from_str_with_warnings(
+        s: &str,
+        path: &Path,
+    ) -> Result<(Self, Vec<ConfigError>), ConfigError> {
+        let mut tokens = lex(s, &format!("{}", path.display()), false);
         let (value_map, type_map) = parser::parse_config(&mut tokens)?;
 
-        ensure! {
-            tokens.is_empty(),
-            anyhow!(tokens.error_current_span(format!("Invalid syntax on config, expected to finish parsing everything but remains: {:?}", tokens.tokens())))
-        };
+        Ok((
+            Values {
+                value_map,
+                type_map,
+            },
+            tokens.warnings,
+        ))
+    }
+}
 
-        Ok(Values {
-            value_map,
-            type_map,
-        })
+/// Lexes `input`, read from `source`, skipping its comments unless
+/// `comments`. Fails on the first invalid token
+fn get_tokens<'a>(input: &'a str, source: &str, comments: bool) -> Result<Tokens<'a>, ConfigError> {
+    let mut tokens = lex(input, source, comments);
+
+    if tokens.warnings.is_empty() {
+        Ok(tokens)
+    } else {
+        Err(tokens.warnings.swap_remove(0))
     }
 }
 
-fn get_tokens<'a>(input: &'a str, source: &str, print: bool) -> Result<Tokens<'a>, anyhow::Error> {
-    let mut tokens: Tokens<'_> = Tokens::new(input, format!("{source}"));
+/// Lexes `input` like [`get_tokens`], skipping the invalid tokens and adding
+/// an error for each of them to the warnings of the tokens
+fn lex<'a>(input: &'a str, source: &str, comments: bool) -> Tokens<'a> {
+    let mut tokens: Tokens<'_> = Tokens::new(input, source);
     let mut lexer = Variant::lexer(input);
     while let Some(token) = lexer.next() {
-        let token = if let Err(()) = token {
-            if !print {
-                return Err(anyhow!("Error reading token"));
-            } else if print && tokens.span.is_empty().not() {
-                crate::error!(tokens.error_current_span("Error reading token"));
-                continue;
-            }
-
-            return Err(anyhow!("Error reading token"));
-        } else {
-            token.expect("Already matched error")
+        let Ok(token) = token else {
+            tokens.warnings.push(ConfigError::Syntax {
+                location: Location::new(Path::new(source), input, lexer.span(), 1),
+                expected: "a valid token".to_string(),
+                found: Some(format!("'{}'", lexer.slice())),
+            });
+            continue;
         };
 
-        if let Variant::Comment(text) = token {
+        if let (Variant::Comment(text), false) = (token, comments) {
             crate::trace!("Skipping comment '{text}'",);
             continue;
         }
@@ -287,5 +347,69 @@ fn get_tokens<'a>(input: &'a str, source: &str, print: bool) -> Result<Tokens<'a
         tokens.token.push(token);
     }
 
-    Ok(tokens)
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Values;
+    use crate::error::ConfigError;
+    use std::path::Path;
+
+    #[test]
+    fn invalid_tokens() {
+        // Invalid tokens in a config are returned as warnings and skipped,
+        // along with keys defined twice
+        let (values, warnings) = Values::from_str_with_warnings(
+            "name = \"temple\" ~\ncount = 3 `\ncount = 4",
+            Path::new("config.tpl"),
+        )
+        .expect("Invalid tokens are skipped");
+
+        assert_eq!(values.value_map["name"], "temple");
+        assert_eq!(values.value_map["count"], 4);
+
+        let lines: Vec<_> = warnings
+            .iter()
+            .map(|warning| warning.location().expect("Warnings have a location").line)
+            .collect();
+        assert_eq!(lines, [1, 2, 3]);
+        assert!(matches!(warnings[2], ConfigError::Duplicate { ref key, .. } if key == "count"));
+
+        // Values given on their own and formatted configs must be valid
+        assert!(Values::parse_value("3 ~", "value").is_err());
+        assert!(super::format("count = 3 ~", Path::new("config.tpl")).is_err());
+    }
+
+    #[test]
+    fn to_json() {
+        let values = Values::from_str(
+            r#"
+            name = "temple"
+            author: { name: String, age: Number, email: String, bio: String } = {
+                name: "ana", age: 30, email: "ana@example.com", bio: ""
+            }
+            tags: [Object { zeta: Bool, alpha: Number }] = []
+            "#,
+            Path::new("config.tpl"),
+        )
+        .expect("Valid config");
+
+        for _ in 0..8 {
+            assert_eq!(
+                serde_json::Value::Object(values.to_json()),
+                serde_json::json!({
+                    "author": {
+                        "type": "Object { age: Number, bio: String, email: String, name: String }",
+                        "value": { "name": "ana", "age": 30, "email": "ana@example.com", "bio": "" },
+                    },
+                    "name": { "type": "Any", "value": "temple" },
+                    "tags": {
+                        "type": "Array [ Object { alpha: Number, zeta: Bool } ]",
+                        "value": [],
+                    },
+                })
+            );
+        }
+    }
 }
@@ -1,17 +1,15 @@
 use std::collections::HashMap;
 
-use anyhow::{anyhow, bail, ensure, Context, Result};
 use tera::{Map, Number, Value};
 
 use crate::{
-    ferror,
+    error::ConfigError,
     values::{Type, TypeMap, ValueMap},
-    warn,
 };
 
 use super::token::{Tokens, Variant};
 
-pub fn try_value_from(tokens: &mut Tokens<'_>) -> Result<Value, anyhow::Error> {
+pub fn try_value_from(tokens: &mut Tokens<'_>) -> Result<Value, ConfigError> {
     match tokens.tokens() {
         &[Variant::String(s), ..] => {
             let s = s.to_string();
@@ -27,53 +25,23 @@ pub fn try_value_from(tokens: &mut Tokens<'_>) -> Result<Value, anyhow::Error> {
             Ok(Value::Number(v.into()))
         }
         &[Variant::FNumber(v), ..] => {
+            let number = Number::from_f64(v).ok_or_else(|| tokens.expected("a finite number"))?;
             tokens.step();
-            Ok(Value::Number(
-                Number::from_f64(v).context("Invalid float value")?,
-            ))
+            Ok(Value::Number(number))
         }
         &[Variant::Bool(v), ..] => {
             tokens.step();
             Ok(Value::Bool(v))
         }
-        [Variant::SqOpen | Variant::CyOpen] => bail!(ferror!(
-            "{}",
-            tokens.error_current_span(format!(
-                "Non closing {}",
-                tokens
-                    .peek()
-                    .expect("We know there is at least one value")
-                    .token
-            ))
-        )),
+        [Variant::SqOpen] => Err(tokens.skipping(1).expected("a value or ']'")),
+        [Variant::CyOpen] => Err(tokens.skipping(1).expected("a key or '}'")),
         [Variant::SqOpen, ..] => parse_list(tokens),
         [Variant::CyOpen, ..] => parse_object(tokens),
-        _ => bail!(ferror!(
-            "{}",
-            tokens.error_current_span(format!(
-                "Found unexpected token {} while trying to parse value",
-                tokens.try_first().map_or_else(
-                    || "'Error getting token. No tokens'".to_string(),
-                    |t| t.token.to_string()
-                )
-            ))
-        )),
+        _ => Err(tokens.expected("a value")),
     }
 }
 
-macro_rules! berr {
-    ($($arg:tt)+) => {
-        bail!(crate::ferror!("{}", $($arg)*))
-    }
-}
-
-macro_rules! aerr {
-    ($($arg:tt)+) => {
-        anyhow!(crate::ferror!("{}", $($arg)*))
-    }
-}
-
-pub fn try_type_from(tokens: &mut Tokens<'_>) -> Result<Type, anyhow::Error> {
+pub fn try_type_from(tokens: &mut Tokens<'_>) -> Result<Type, ConfigError> {
     match tokens.tokens() {
         [Variant::KwAny, ..] => {
             tokens.step();
@@ -95,14 +63,11 @@ pub fn try_type_from(tokens: &mut Tokens<'_>) -> Result<Type, anyhow::Error> {
         [Variant::KwObject, ..] => parse_object_type(tokens.skipping(1)),
         [Variant::SqOpen, ..] => parse_array_type(tokens),
         [Variant::CyOpen, ..] => parse_object_type(tokens),
-        _ => berr!(tokens.error_current_span(format!(
-            "Found unexpected token {:?} while trying to parse data type",
-            tokens.try_first().map(|t| t.token)
-        ))),
+        _ => Err(tokens.expected("a data type")),
     }
 }
 
-pub fn parse_object_type(tokens: &mut Tokens<'_>) -> Result<Type, anyhow::Error> {
+pub fn parse_object_type(tokens: &mut Tokens<'_>) -> Result<Type, ConfigError> {
     if matches!(tokens.peek().map(|e| e.token), Some(Variant::CyOpen)) {
         tokens.step();
 
@@ -117,58 +82,48 @@ pub fn parse_object_type(tokens: &mut Tokens<'_>) -> Result<Type, anyhow::Error>
                 let ident = tokens.get_ident().expect("Just matched");
                 let value = try_type_from(tokens.skipping(2))?;
 
-                res.insert(ident.to_string(), value).is_some().then(|| {
-                    warn!(tokens.error_at(
-                        loc,
-                        format!("Ident `{ident}` is already defined, overriding")
-                    ));
-                });
+                if res.insert(ident.to_string(), value).is_some() {
+                    tokens.warnings.push(ConfigError::Duplicate {
+                        location: loc,
+                        key: ident,
+                    });
+                }
 
                 if let [Variant::Comma, ..] = tokens.tokens() {
                     tokens.step();
                 }
             }
 
-            ensure!(
-                tokens.peek().map(|v| v.token) == Some(&Variant::CyClose),
-                aerr!(tokens.error_current_span(format!(
-                    "Expected closing '}}' in Object type declaration but found {:?}",
-                    tokens.peek().map(|v| v.token)
-                )))
-            );
+            if tokens.peek().map(|v| v.token) != Some(&Variant::CyClose) {
+                return Err(tokens.expected("closing '}' in Object type declaration"));
+            }
 
             tokens.step();
             Ok(Type::Object(res))
         }
     } else {
-        Err(aerr!(tokens.error_current_span(
-            "Token is not an object type declaration",
-        )))
+        Err(tokens.expected("an Object type declaration"))
     }
 }
 
-pub fn parse_array_type(tokens: &mut Tokens<'_>) -> Result<Type, anyhow::Error> {
+pub fn parse_array_type(tokens: &mut Tokens<'_>) -> Result<Type, ConfigError> {
     if let [Variant::SqOpen, ..] = tokens.tokens() {
         tokens.step();
 
         let typ = try_type_from(tokens)?;
 
-        ensure!(
-            tokens.peek().map(|v| v.token) == Some(&Variant::SqClose),
-            aerr!(tokens
-                .error_current_span("Expected ']' after type {typ} in Array type declaration"))
-        );
+        if tokens.peek().map(|v| v.token) != Some(&Variant::SqClose) {
+            return Err(tokens.expected(format!("']' after type {typ} in Array type declaration")));
+        }
 
         tokens.step();
         Ok(Type::Array(Box::new(typ)))
     } else {
-        Err(aerr!(tokens.error_current_span(
-            "Expected '[' before type Array type declaration",
-        )))
+        Err(tokens.expected("'[' before the type in Array type declaration"))
     }
 }
 
-pub fn parse_config(tokens: &mut Tokens<'_>) -> Result<(ValueMap, TypeMap), anyhow::Error> {
+pub fn parse_config(tokens: &mut Tokens<'_>) -> Result<(ValueMap, TypeMap), ConfigError> {
     let mut values = ValueMap::default();
     let mut types = TypeMap::default();
 
@@ -186,10 +141,8 @@ pub fn parse_config(tokens: &mut Tokens<'_>) -> Result<(ValueMap, TypeMap), anyh
                     types.insert(ident.to_string(), typ);
 
                     Some(types.get(ident.as_str()).expect("just pushed the value"))
-                } else if let [Variant::EqD, token, ..] = tokens.tokens() {
-                    berr!(tokens.error_current_span(format!(
-                        "Unexpected token {token} ({token:?}) following ':' in type declaration"
-                    )))
+                } else if let [Variant::EqD, ..] = tokens.tokens() {
+                    return Err(tokens.skipping(1).expected("a data type following ':'"));
                 } else {
                     types.insert(ident.to_string(), Type::Any);
                     None
@@ -198,26 +151,27 @@ pub fn parse_config(tokens: &mut Tokens<'_>) -> Result<(ValueMap, TypeMap), anyh
                 if let [Variant::Eq, ..] = tokens.tokens() {
                     let value = try_value_from(tokens.skipping(1))?;
 
-                    values.insert(ident.to_string(), value).is_some().then(|| {
-                        warn!(tokens.error_at(
-                            loc,
-                            format!("Ident `{ident}` is already defined, overriding")
-                        ));
-                    });
+                    if values.insert(ident.to_string(), value).is_some() {
+                        tokens.warnings.push(ConfigError::Duplicate {
+                            location: loc,
+                            key: ident,
+                        });
+                    }
                 } else {
-                    values
-                        .insert(ident.to_string(), Value::Null)
-                        .is_some()
-                        .then(|| {
-                            warn!(tokens.error_at(
-                                loc,
-                                format!("Ident `{ident}` is already defined, overriding")
-                            ));
+                    let is_typed = typ.is_some();
+
+                    if values.insert(ident.to_string(), Value::Null).is_some() {
+                        tokens.warnings.push(ConfigError::Duplicate {
+                            location: loc,
+                            key: ident.clone(),
                         });
+                    }
 
-                    if typ.is_none() {
-                        warn!(tokens
-                            .error_current_span("We advise to state a data type for empty values"));
+                    if !is_typed {
+                        tokens.warnings.push(ConfigError::Untyped {
+                            location: tokens.location(tokens.cursor - 1),
+                            key: ident,
+                        });
                     }
                 }
 
@@ -225,30 +179,14 @@ pub fn parse_config(tokens: &mut Tokens<'_>) -> Result<(ValueMap, TypeMap), anyh
                     tokens.step();
                 }
             }
-            &[token, ..] => {
-                berr!(tokens.error_current_span(format!("Found unexpected token {token:?}")))
-            }
-            _ => {
-                berr!(tokens.error_current_span("Expected token, found nothing"),)
-            }
+            _ => return Err(tokens.expected("a key followed by '=' or ':'")),
         }
     }
 
-    ensure! {
-        tokens.is_empty(),
-        aerr!(
-            tokens.error_current_span(
-                format!("Invalid syntax on config, expected to finish parsing everything but remains: {:?}",
-                    tokens.tokens()
-                )
-            )
-        ),
-    };
-
     Ok((values, types))
 }
 
-pub fn parse_object(tokens: &mut Tokens<'_>) -> Result<Value, anyhow::Error> {
+pub fn parse_object(tokens: &mut Tokens<'_>) -> Result<Value, ConfigError> {
     if let [Variant::CyOpen, ..] = tokens.tokens() {
         tokens.step();
 
@@ -258,48 +196,49 @@ pub fn parse_object(tokens: &mut Tokens<'_>) -> Result<Value, anyhow::Error> {
         } else {
             let mut res = Map::new();
 
-            while let &[Variant::Ident(_), Variant::Eq | Variant::EqD, ..] = tokens.tokens() {
+            // Keys that are not valid identifiers, like globs, can be quoted
+            while let &[Variant::Ident(ident) | Variant::String(ident), Variant::Eq | Variant::EqD, ..] =
+                tokens.tokens()
+            {
                 let loc = tokens.current_location();
-                let ident = tokens.get_ident().expect("Just matched");
+                let ident = ident.to_string();
                 let value = try_value_from(tokens.skipping(2))?;
 
-                res.insert(ident.to_string(), value).is_some().then(|| {
-                    warn!(tokens.error_at(
-                        loc,
-                        format!("Ident `{ident}` is already defined, overriding")
-                    ));
-                });
+                if res.insert(ident.to_string(), value).is_some() {
+                    tokens.warnings.push(ConfigError::Duplicate {
+                        location: loc,
+                        key: ident,
+                    });
+                }
 
                 if let [Variant::Comma, ..] = tokens.tokens() {
                     tokens.step();
                 }
             }
 
-            ensure!(
-                tokens.peek().map(|e| e.token) == Some(&Variant::CyClose),
-                aerr!(tokens.error_current_span(format!(
-                    "Expected closing '}}' in Object declaration but found {:?}",
-                    tokens.peek().map(|v| v.token)
-                )))
-            );
+            if tokens.peek().map(|e| e.token) != Some(&Variant::CyClose) {
+                return Err(tokens.expected("closing '}' in Object declaration"));
+            }
 
             tokens.step();
 
             Ok(Value::Object(res))
         }
     } else {
-        Err(aerr!(
-            tokens.error_current_span("Token is not an object declaration")
-        ))
+        Err(tokens.expected("an Object declaration"))
     }
 }
 
-fn parse_list(tokens: &mut Tokens<'_>) -> Result<Value, anyhow::Error> {
+fn parse_list(tokens: &mut Tokens<'_>) -> Result<Value, ConfigError> {
     if let &[Variant::SqOpen, ..] = tokens.tokens() {
         tokens.step();
 
         if !tokens.tokens().contains(&Variant::SqClose) {
-            berr!(tokens.error_current_span("Array not closed"))
+            return Err(ConfigError::Syntax {
+                location: tokens.location(tokens.cursor - 1),
+                expected: "closing ']' in Array declaration".to_string(),
+                found: None,
+            });
         }
 
         if let &[Variant::SqClose, ..] = tokens.tokens() {
@@ -315,18 +254,14 @@ fn parse_list(tokens: &mut Tokens<'_>) -> Result<Value, anyhow::Error> {
                         list.push(value);
                     }
                     (false, [Variant::Comma, Variant::Comma, ..]) => {
-                        berr!(tokens.error_current_span("There must be a value between commas"))
+                        return Err(tokens.skipping(1).expected("a value between commas"))
                     }
                     (false, [Variant::SqClose, ..]) => {
                         tokens.step();
                         break;
                     }
                     (false, [Variant::Comma, ..]) => tokens.step(),
-                    (false, [token, ..]) => {
-                        berr!(tokens.error_current_span(format!(
-                            "Invalid token in list declaration: `{token:?}`"
-                        )))
-                    }
+                    (false, [_, ..]) => return Err(tokens.expected("a value, ',' or ']'")),
                     (_, []) => unreachable!("We did check the list is not empty"),
                 }
             }
@@ -338,6 +273,6 @@ fn parse_list(tokens: &mut Tokens<'_>) -> Result<Value, anyhow::Error> {
             Ok(Value::Array(list))
         }
     } else {
-        Err(aerr!(tokens.error_current_span("Token is not a list",)))
+        Err(tokens.expected("an Array declaration"))
     }
 }
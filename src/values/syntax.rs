@@ -0,0 +1,457 @@
+use std::path::Path;
+
+use crate::error::ConfigError;
+
+use super::{
+    get_tokens,
+    token::{Tokens, Variant},
+    writer::{self, Comments, Document, Entry, Item, Items, Node},
+};
+
+/// Reads the syntax tree of a config, keeping its comments. It accepts the
+/// same syntax [`super::parser::parse_config`] does, normalizing what the
+/// config syntax allows to write in several ways, like the casing of
+/// keywords, the quotes of strings or the separators between entries
+struct Parser<'i> {
+    tokens: Tokens<'i>,
+    /// Comments read that are not attached to an item yet
+    pending: Comments,
+}
+
+impl<'i> Parser<'i> {
+    /// Marks a blank line in the pending comments if there is one before the
+    /// token at the cursor
+    fn blank_line(&mut self) {
+        let Some(prev) = self.tokens.cursor.checked_sub(1) else {
+            return;
+        };
+
+        let end = self.tokens.span[prev].end;
+        let start = self
+            .tokens
+            .span
+            .get(self.tokens.cursor)
+            .map_or(self.tokens.inp.len(), |span| span.start);
+
+        if self.tokens.inp[end..start].matches('\n').count() > 1
+            && self.pending.last().map(String::as_str) != Some("")
+        {
+            self.pending.push(String::new());
+        }
+    }
+
+    /// Moves the comments at the cursor to the pending ones
+    fn skip_comments(&mut self) {
+        self.blank_line();
+
+        while let [Variant::Comment(comment), ..] = self.tokens.tokens() {
+            self.pending.push(comment.trim_end().to_string());
+            self.tokens.step();
+            self.blank_line();
+        }
+    }
+
+    /// Takes the pending comments, without the blank lines at their end when
+    /// they are the last ones of a block
+    fn take_comments(&mut self, last: bool) -> Comments {
+        let mut comments = std::mem::take(&mut self.pending);
+
+        while last && comments.last().is_some_and(String::is_empty) {
+            comments.pop();
+        }
+
+        comments
+    }
+
+    /// Moves the comments found in between the tokens of an item to the ones
+    /// before it
+    fn move_pending(&mut self, comments: &mut Comments) {
+        comments.extend(self.pending.drain(..).filter(|line| !line.is_empty()));
+    }
+
+    /// Returns the comment right after the last token read, in its same line
+    fn trailing(&mut self) -> Option<String> {
+        let &[Variant::Comment(comment), ..] = &self.tokens.token[self.tokens.cursor..] else {
+            return None;
+        };
+
+        let end = self.tokens.span[self.tokens.cursor - 1].end;
+        let start = self.tokens.span[self.tokens.cursor].start;
+
+        if self.tokens.inp[end..start].contains('\n') {
+            return None;
+        }
+
+        self.tokens.step();
+        Some(comment.trim_end().to_string())
+    }
+
+    /// Returns the next `n` tokens that are not comments
+    fn peek(&self, n: usize) -> Vec<Variant<'i>> {
+        self.tokens.token[self.tokens.cursor..]
+            .iter()
+            .filter(|token| !matches!(token, Variant::Comment(_)))
+            .take(n)
+            .copied()
+            .collect()
+    }
+
+    /// Consumes the next token that is not a comment
+    fn step(&mut self) {
+        self.skip_comments();
+        self.tokens.step();
+    }
+
+    /// Returns the string `s` of the next token between quotes, keeping the
+    /// quotes it was written with when it can not be quoted canonically
+    fn quoted(&self, s: &str) -> String {
+        writer::quote(s).unwrap_or_else(|_| {
+            let index = self.tokens.token[self.tokens.cursor..]
+                .iter()
+                .position(|token| !matches!(token, Variant::Comment(_)))
+                .expect("The string is the next token");
+
+            self.tokens.inp[self.tokens.span[self.tokens.cursor + index].clone()].to_owned()
+        })
+    }
+
+    fn expected(&mut self, expected: impl Into<String>) -> ConfigError {
+        self.skip_comments();
+        self.tokens.expected(expected)
+    }
+
+    fn document(&mut self) -> Result<Document, ConfigError> {
+        let mut document = Document::default();
+
+        loop {
+            self.skip_comments();
+
+            if self.tokens.is_empty() {
+                break;
+            }
+
+            document.entries.push(self.entry()?);
+        }
+
+        document.comments = self.take_comments(true);
+        Ok(document)
+    }
+
+    fn entry(&mut self) -> Result<Entry, ConfigError> {
+        let [Variant::Ident(key), Variant::Eq | Variant::EqD] = self.peek(2)[..] else {
+            return Err(self.expected("a key followed by '=' or ':'"));
+        };
+
+        let mut comments = self.take_comments(false);
+        self.step();
+
+        let typ = if let [Variant::EqD, token] = self.peek(2)[..] {
+            if !token.is_type_decl() {
+                self.step();
+                return Err(self.expected("a data type following ':'"));
+            }
+
+            self.step();
+            Some(self.type_node()?)
+        } else {
+            None
+        };
+
+        let value = if let [Variant::Eq] = self.peek(1)[..] {
+            self.step();
+            Some(self.value_node()?)
+        } else {
+            None
+        };
+
+        if let [Variant::Comma | Variant::Semicolon] = self.peek(1)[..] {
+            self.step();
+        }
+
+        self.move_pending(&mut comments);
+
+        Ok(Entry {
+            comments,
+            key: key.to_string(),
+            typ,
+            value,
+            trailing: self.trailing(),
+        })
+    }
+
+    fn type_node(&mut self) -> Result<Node, ConfigError> {
+        let keyword = |keyword: &str| Ok(Node::Atom(keyword.to_string()));
+
+        match self.peek(2)[..] {
+            [Variant::KwAny, ..] => {
+                self.step();
+                keyword("Any")
+            }
+            [Variant::KwNumber, ..] => {
+                self.step();
+                keyword("Number")
+            }
+            [Variant::KwString, ..] => {
+                self.step();
+                keyword("String")
+            }
+            [Variant::KwBool, ..] => {
+                self.step();
+                keyword("Bool")
+            }
+            [Variant::KwArray, Variant::SqOpen] | [Variant::SqOpen, ..] => {
+                if let [Variant::KwArray] = self.peek(1)[..] {
+                    self.step();
+                }
+
+                self.step();
+                let node = self.type_node()?;
+
+                if self.peek(1)[..] != [Variant::SqClose] {
+                    return Err(self.expected("']' after the type in Array type declaration"));
+                }
+
+                self.step();
+                Ok(Node::ArrayType(Box::new(node)))
+            }
+            [Variant::KwArray, ..] => {
+                self.step();
+                Err(self.expected("'[' before the type in Array type declaration"))
+            }
+            [Variant::KwObject, Variant::CyOpen] | [Variant::CyOpen, ..] => {
+                if let [Variant::KwObject] = self.peek(1)[..] {
+                    self.step();
+                }
+
+                self.step();
+                Ok(Node::Object(self.fields(true)?))
+            }
+            [Variant::KwObject, ..] => {
+                self.step();
+                Err(self.expected("an Object type declaration"))
+            }
+            _ => Err(self.expected("a data type")),
+        }
+    }
+
+    fn value_node(&mut self) -> Result<Node, ConfigError> {
+        let Some(&token) = self.peek(1).first() else {
+            return Err(self.expected("a value"));
+        };
+
+        let atom = match token {
+            Variant::String(s) => self.quoted(s),
+            Variant::UNumber(n) => n.to_string(),
+            Variant::SNumber(n) => n.to_string(),
+            Variant::FNumber(n) => {
+                let number =
+                    tera::Number::from_f64(n).ok_or_else(|| self.expected("a finite number"))?;
+                writer::number(&number)
+            }
+            Variant::Bool(bool) => bool.to_string(),
+            Variant::SqOpen => {
+                self.step();
+                return Ok(Node::Array(self.elements()?));
+            }
+            Variant::CyOpen => {
+                self.step();
+                return Ok(Node::Object(self.fields(false)?));
+            }
+            _ => return Err(self.expected("a value")),
+        };
+
+        self.step();
+        Ok(Node::Atom(atom))
+    }
+
+    /// Reads the fields of an object value or an object type, up to its `}`
+    fn fields(&mut self, is_type: bool) -> Result<Items, ConfigError> {
+        let mut items = Vec::new();
+
+        loop {
+            self.skip_comments();
+
+            let key = match self.peek(2)[..] {
+                [Variant::Ident(key), Variant::EqD] if is_type => key.to_string(),
+                [Variant::Ident(key), Variant::Eq | Variant::EqD] if !is_type => key.to_string(),
+                // Keys that are not valid identifiers, like globs, can be quoted
+                [Variant::String(key), Variant::Eq | Variant::EqD] if !is_type => {
+                    if writer::is_ident(key) {
+                        key.to_string()
+                    } else {
+                        self.quoted(key)
+                    }
+                }
+                _ => break,
+            };
+
+            let mut comments = self.take_comments(false);
+            self.step();
+            self.step();
+
+            let node = if is_type {
+                self.type_node()?
+            } else {
+                self.value_node()?
+            };
+
+            if let [Variant::Comma] = self.peek(1)[..] {
+                self.step();
+            }
+
+            self.move_pending(&mut comments);
+
+            items.push(Item {
+                comments,
+                key: Some(key),
+                node,
+                trailing: self.trailing(),
+            });
+        }
+
+        if self.peek(1)[..] != [Variant::CyClose] {
+            return Err(self.expected(if is_type {
+                "closing '}' in Object type declaration"
+            } else {
+                "closing '}' in Object declaration"
+            }));
+        }
+
+        let comments = self.take_comments(true);
+        self.step();
+
+        Ok(Items { items, comments })
+    }
+
+    /// Reads the elements of an array value, up to its `]`
+    fn elements(&mut self) -> Result<Items, ConfigError> {
+        let mut items = Vec::<Item>::new();
+
+        loop {
+            self.skip_comments();
+
+            match self.peek(2)[..] {
+                [token, ..] if token.is_expr_decl() => {
+                    let mut comments = self.take_comments(false);
+                    let node = self.value_node()?;
+
+                    self.move_pending(&mut comments);
+
+                    items.push(Item {
+                        comments,
+                        key: None,
+                        node,
+                        trailing: self.trailing(),
+                    });
+                }
+                [Variant::Comma, Variant::Comma] => {
+                    self.step();
+                    return Err(self.expected("a value between commas"));
+                }
+                [Variant::Comma, ..] => {
+                    self.step();
+
+                    if let Some(trailing) = self.trailing() {
+                        match items.last_mut() {
+                            Some(last) if last.trailing.is_none() => last.trailing = Some(trailing),
+                            _ => self.pending.push(trailing),
+                        }
+                    }
+                }
+                [Variant::SqClose, ..] => break,
+                [] => return Err(self.expected("closing ']' in Array declaration")),
+                [..] => return Err(self.expected("a value, ',' or ']'")),
+            }
+        }
+
+        let comments = self.take_comments(true);
+        self.step();
+
+        Ok(Items { items, comments })
+    }
+}
+
+/// Formats the config `s`, read from `path`, canonically. Keys are written
+/// one per line as `key: Type = value` with keywords in their canonical
+/// casing, and arrays and objects that do not fit in a line are written one
+/// item per line with a trailing comma, aligning the values of objects.
+/// Comments and single blank lines between entries are kept.
+///
+/// # Errors
+///
+/// Returns an [`Err`] if `s` is not a valid config
+pub fn format(s: &str, path: &Path) -> Result<String, ConfigError> {
+    let mut parser = Parser {
+        tokens: get_tokens(s, &path.display().to_string(), true)?,
+        pending: Comments::new(),
+    };
+
+    Ok(parser.document()?.write())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format;
+    use crate::values::Values;
+    use std::path::Path;
+
+    #[test]
+    fn format_config() {
+        let config = r#"# Template config
+
+name:string="temple" # the name
+count=3;  tags : ARRAY[ string ] = [ 'a',"b", ]
+
+
+# The author
+author :OBJECT{ name:String,age : number }={name:"ana",
+    # In years
+    age:30}
+paths = { "*.rs": "rust", 'src': 1. }
+flags = [TRUE, false, # last
+]
+quotes = { 'say "hi"
+to it': "it's" }
+# end
+"#;
+
+        let formatted = format(config, Path::new("config.tpl")).expect("Valid config");
+
+        assert_eq!(
+            formatted,
+            r#"# Template config
+
+name: String = "temple" # the name
+count = 3
+tags: [String] = ["a", "b"]
+
+# The author
+author: { name: String, age: Number } = {
+    name: "ana",
+    # In years
+    age:  30,
+}
+paths = { "*.rs": "rust", src: 1.0 }
+flags = [
+    true,
+    false, # last
+]
+quotes = { 'say "hi"
+to it': "it's" }
+# end
+"#
+        );
+
+        assert_eq!(
+            format(&formatted, Path::new("config.tpl")).expect("Valid config"),
+            formatted
+        );
+        assert_eq!(
+            Values::from_str(&formatted, Path::new("config.tpl")).expect("Valid config"),
+            Values::from_str(config, Path::new("config.tpl")).expect("Valid config")
+        );
+
+        assert!(format("name: = 1", Path::new("config.tpl")).is_err());
+        assert!(format("list = [1,, 2]", Path::new("config.tpl")).is_err());
+    }
+}
@@ -1,6 +1,9 @@
+use std::path::Path;
+
 pub use logos::Logos;
 use logos::Span;
-use owo_colors::OwoColorize;
+
+use crate::error::{ConfigError, Location};
 
 #[derive(Debug, Default)]
 pub struct Tokens<'i> {
@@ -9,6 +12,9 @@ pub struct Tokens<'i> {
     pub span: Vec<Span>,
     pub token: Vec<Variant<'i>>,
     pub cursor: usize,
+    /// Problems found while reading the tokens that do not stop parsing,
+    /// like invalid tokens that were skipped or keys defined twice
+    pub warnings: Vec<ConfigError>,
 }
 
 pub struct Token<'i> {
@@ -25,45 +31,6 @@ pub struct Peek<'re, 'i> {
     pub token: &'re Variant<'i>,
 }
 
-struct Underlined(Span);
-
-impl std::fmt::Display for Underlined {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let span = self.0.clone();
-
-        for _ in 0..(span.start - 1) {
-            write!(f, " ")?;
-        }
-
-        write!(
-            f,
-            "{}",
-            "^".repeat(span.end - span.start)
-                .if_supports_color(owo_colors::Stream::Stdout, |s| {
-                    s.style(owo_colors::Style::new().bold().yellow())
-                }),
-        )
-    }
-}
-
-pub struct Location(Span, usize);
-
-impl From<(Span, usize)> for Location {
-    fn from((span, line): (Span, usize)) -> Self {
-        Location(span, line)
-    }
-}
-
-impl std::fmt::Display for Location {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}:{}-{}", self.1, self.0.start, self.0.end)
-    }
-}
-
-fn get_line(inp: &str, line: usize) -> &str {
-    inp.lines().nth(line - 1).unwrap_or_default()
-}
-
 impl Tokens<'_> {
     #[allow(clippy::field_reassign_with_default)]
     pub fn new(inp: &str, path: impl Into<String>) -> Tokens<'_> {
@@ -73,65 +40,31 @@ impl Tokens<'_> {
         res
     }
 
-    pub fn error_current_span(&self, msg: impl Into<String>) -> String {
-        self.error_at(self.current_location(), msg)
-    }
-
-    pub fn current_location(&self) -> Location {
-        self.location(self.cursor.checked_sub(1).unwrap_or_default())
-    }
-
+    /// Returns the location of the token at `cursor`, or of the last one when
+    /// all were consumed
     pub fn location(&self, cursor: usize) -> Location {
         let span = if let Some(loc) = self.span.get(cursor).cloned() {
             loc
-        } else if !self.span.is_empty() {
-            self.span[cursor - 1].clone()
+        } else if let Some(last) = self.span.last() {
+            last.end..last.end
         } else {
             0..self.inp.len()
         };
 
-        let mut line = 1;
-        let mut col = 1;
-        for i in 0..span.start {
-            if self
-                .inp
-                .chars()
-                .nth(i)
-                .expect("Logos returns valid spans always")
-                == '\n'
-            {
-                line += 1;
-                col = 1;
-            } else {
-                col += 1;
-            }
-        }
+        Location::new(Path::new(&self.path), self.inp, span, 1)
+    }
 
-        (col..col + (span.end - span.start), line).into()
+    /// Returns the location of the token about to be consumed
+    pub fn current_location(&self) -> Location {
+        self.location(self.cursor)
     }
 
-    #[allow(clippy::needless_pass_by_value)]
-    pub fn error_at(&self, location: Location, msg: impl Into<String>) -> String {
-        if self.is_empty() {
-            String::new()
-        } else {
-            format!(
-                "{msg}\n   {arrow}{path}:{line}:{start}\n{empty_pipe}\n{bline} {contents}\n{empty_pipe} {underline}",
-                msg = msg.into(),
-                arrow = "--> ".if_supports_color(owo_colors::Stream::Stdout, |s| s
-                    .style(owo_colors::Style::new().bold().blue())),
-                empty_pipe = format!("{: >3} |", "")
-                    .if_supports_color(owo_colors::Stream::Stdout, |s| s
-                    .style(owo_colors::Style::new().bold().blue())),
-                bline = format!("{line: >3} |", line = location.1)
-                    .if_supports_color(owo_colors::Stream::Stdout, |s| s
-                    .style(owo_colors::Style::new().bold().blue())),
-                path = self.path,
-                line = location.1,
-                start = location.0.start,
-                contents = get_line(self.inp, location.1),
-                underline = Underlined(location.0.clone())
-            )
+    /// Returns a syntax error at the current token, which is reported as found
+    pub fn expected(&self, expected: impl Into<String>) -> ConfigError {
+        ConfigError::Syntax {
+            location: self.current_location(),
+            expected: expected.into(),
+            found: self.try_first().map(|t| t.token.to_string()),
         }
     }
 
@@ -298,6 +231,8 @@ impl Variant<'_> {
             Variant::String(_)
                 | Variant::UNumber(_)
                 | Variant::SNumber(_)
+                | Variant::FNumber(_)
+                | Variant::Bool(_)
                 | Variant::SqOpen
                 | Variant::CyOpen
         )
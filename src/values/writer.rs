@@ -0,0 +1,466 @@
+use std::fmt::Write;
+use tera::Value;
+
+use crate::error::ConfigError;
+
+use super::{
+    token::{Logos, Variant},
+    Type, Values,
+};
+
+/// Columns a line may take before arrays and objects are split in several
+const WIDTH: usize = 80;
+const INDENT: &str = "    ";
+
+/// Piece of the config syntax, laid out by [`Node::write`]
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Node {
+    /// Text written as is, like a value or a type keyword
+    Atom(String),
+    /// `[T]`, the type of the elements of an array
+    ArrayType(Box<Node>),
+    Array(Items),
+    /// An object value or an object type, with the key of every item
+    Object(Items),
+}
+
+/// Comments are kept as the lines they take, where an empty line stands
+/// for a blank line between comments, items or entries
+pub(super) type Comments = Vec<String>;
+
+/// Items of an array or an object, with the comments after the last one
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(super) struct Items {
+    pub items: Vec<Item>,
+    pub comments: Comments,
+}
+
+/// Element of an array or field of an object
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Item {
+    /// Comments in the lines before the item
+    pub comments: Comments,
+    pub key: Option<String>,
+    pub node: Node,
+    /// Comment after the item, in its same line
+    pub trailing: Option<String>,
+}
+
+/// Key of a config, with its type and its value
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Entry {
+    /// Comments in the lines before the entry
+    pub comments: Comments,
+    pub key: String,
+    pub typ: Option<Node>,
+    pub value: Option<Node>,
+    /// Comment after the entry, in its same line
+    pub trailing: Option<String>,
+}
+
+/// Entries of a config file, with the comments after the last one
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(super) struct Document {
+    pub entries: Vec<Entry>,
+    pub comments: Comments,
+}
+
+/// Returns the comments of the first item of a block without the blank
+/// lines before them
+fn without_leading_blanks(comments: &[String], first: bool) -> &[String] {
+    let blanks = if first {
+        comments.iter().take_while(|line| line.is_empty()).count()
+    } else {
+        0
+    };
+
+    &comments[blanks..]
+}
+
+fn has_comments(comments: &[String]) -> bool {
+    comments.iter().any(|line| !line.is_empty())
+}
+
+impl Item {
+    pub fn new(key: Option<String>, node: Node) -> Self {
+        Self {
+            comments: Comments::new(),
+            key,
+            node,
+            trailing: None,
+        }
+    }
+
+    fn inline(&self) -> Option<String> {
+        if has_comments(&self.comments) || self.trailing.is_some() {
+            return None;
+        }
+
+        let node = self.node.inline()?;
+
+        Some(match &self.key {
+            Some(key) => format!("{key}: {node}"),
+            None => node,
+        })
+    }
+}
+
+impl Node {
+    /// Returns the node written in a single line, if it has no comments
+    fn inline(&self) -> Option<String> {
+        let items = |items: &Items| {
+            if has_comments(&items.comments) {
+                return None;
+            }
+
+            items
+                .items
+                .iter()
+                .map(Item::inline)
+                .collect::<Option<Vec<_>>>()
+                .map(|items| items.join(", "))
+        };
+
+        Some(match self {
+            Node::Atom(text) => text.clone(),
+            Node::ArrayType(node) => format!("[{}]", node.inline()?),
+            Node::Array(elements) => format!("[{}]", items(elements)?),
+            Node::Object(fields) if fields.items.is_empty() && fields.comments.is_empty() => {
+                "{}".to_string()
+            }
+            Node::Object(fields) => format!("{{ {} }}", items(fields)?),
+        })
+    }
+
+    /// Writes the node at the end of `out`, which is indented `indent` levels.
+    /// Arrays and objects that do not fit in the line or have comments are
+    /// written one item per line with a trailing comma, with the values of
+    /// objects aligned
+    pub fn write(&self, out: &mut String, indent: usize) {
+        let column = out
+            .rsplit('\n')
+            .next()
+            .map_or(0, |line| line.chars().count());
+
+        if let Some(inline) = self.inline() {
+            if column + inline.chars().count() <= WIDTH || matches!(self, Node::Atom(_)) {
+                out.push_str(&inline);
+                return;
+            }
+        }
+
+        match self {
+            Node::Atom(text) => out.push_str(text),
+            Node::ArrayType(node) => {
+                out.push('[');
+                node.write(out, indent);
+                out.push(']');
+            }
+            Node::Array(items) | Node::Object(items) => {
+                let (open, close) = if matches!(self, Node::Array(_)) {
+                    ('[', ']')
+                } else {
+                    ('{', '}')
+                };
+
+                let width = items
+                    .items
+                    .iter()
+                    .filter_map(|item| item.key.as_ref())
+                    .map(|key| key.chars().count())
+                    .max()
+                    .unwrap_or_default();
+
+                let write_comments = |out: &mut String, comments: &[String]| {
+                    for line in comments {
+                        out.push('\n');
+                        if !line.is_empty() {
+                            out.push_str(&INDENT.repeat(indent + 1));
+                            out.push_str(line);
+                        }
+                    }
+                };
+
+                out.push(open);
+
+                for (i, item) in items.items.iter().enumerate() {
+                    write_comments(out, without_leading_blanks(&item.comments, i == 0));
+
+                    out.push('\n');
+                    out.push_str(&INDENT.repeat(indent + 1));
+
+                    if let Some(key) = &item.key {
+                        let pad = width - key.chars().count();
+                        let _ = write!(out, "{key}: {: <pad$}", "");
+                    }
+
+                    item.node.write(out, indent + 1);
+                    out.push(',');
+
+                    if let Some(trailing) = &item.trailing {
+                        let _ = write!(out, " {trailing}");
+                    }
+                }
+
+                write_comments(
+                    out,
+                    without_leading_blanks(&items.comments, items.items.is_empty()),
+                );
+
+                out.push('\n');
+                out.push_str(&INDENT.repeat(indent));
+                out.push(close);
+            }
+        }
+    }
+}
+
+impl Document {
+    /// Writes the entries one per line, as `key: Type = value`
+    pub fn write(&self) -> String {
+        let mut out = String::new();
+
+        for (i, entry) in self.entries.iter().enumerate() {
+            for line in without_leading_blanks(&entry.comments, i == 0) {
+                out.push_str(line);
+                out.push('\n');
+            }
+
+            out.push_str(&entry.key);
+
+            if let Some(typ) = &entry.typ {
+                out.push_str(": ");
+                typ.write(&mut out, 0);
+            }
+
+            if let Some(value) = &entry.value {
+                out.push_str(" = ");
+                value.write(&mut out, 0);
+            }
+
+            if let Some(trailing) = &entry.trailing {
+                let _ = write!(out, " {trailing}");
+            }
+
+            out.push('\n');
+        }
+
+        for line in without_leading_blanks(&self.comments, self.entries.is_empty()) {
+            out.push_str(line);
+            out.push('\n');
+        }
+
+        out
+    }
+}
+
+/// Returns whether `key` can be written without quotes, which are only
+/// allowed for the keys of object values
+pub(super) fn is_ident(key: &str) -> bool {
+    matches!(
+        Variant::lexer(key).collect::<Vec<_>>().as_slice(),
+        [Ok(Variant::Ident(ident))] if *ident == key
+    )
+}
+
+/// Returns `s` between quotes. There is no escaping in strings, so those
+/// containing both kinds of quotes can not be written
+pub(super) fn quote(s: &str) -> Result<String, String> {
+    if !s.contains('"') {
+        Ok(format!("\"{s}\""))
+    } else if !s.contains('\'') {
+        Ok(format!("'{s}'"))
+    } else {
+        Err(format!("the string {s:?} contains both `\"` and `'`"))
+    }
+}
+
+pub(super) fn number(number: &tera::Number) -> String {
+    if let Some(n) = number.as_u64() {
+        n.to_string()
+    } else if let Some(n) = number.as_i64() {
+        n.to_string()
+    } else {
+        // Floats need a dot to not be read back as integers
+        let n = number.as_f64().unwrap_or_default().to_string();
+        if n.contains('.') {
+            n
+        } else {
+            format!("{n}.0")
+        }
+    }
+}
+
+fn type_node(typ: &Type) -> Result<Node, String> {
+    Ok(match typ {
+        Type::Number => Node::Atom("Number".to_string()),
+        Type::String => Node::Atom("String".to_string()),
+        Type::Bool => Node::Atom("Bool".to_string()),
+        Type::Any => Node::Atom("Any".to_string()),
+        Type::Unknown => return Err("its type is unknown".to_string()),
+        Type::Array(elements) => Node::ArrayType(Box::new(type_node(elements)?)),
+        Type::Object(fields) => {
+            let mut keys = fields.keys().collect::<Vec<_>>();
+            keys.sort();
+
+            let items = keys
+                .into_iter()
+                .map(|key| {
+                    if !is_ident(key) {
+                        return Err(format!("the field `{key}` is not a valid identifier"));
+                    }
+
+                    Ok(Item::new(Some(key.clone()), type_node(&fields[key])?))
+                })
+                .collect::<Result<_, _>>()?;
+
+            Node::Object(Items {
+                items,
+                comments: Comments::new(),
+            })
+        }
+    })
+}
+
+fn value_node(value: &Value) -> Result<Node, String> {
+    Ok(match value {
+        Value::Null => return Err("null values have no syntax".to_string()),
+        Value::Bool(bool) => Node::Atom(bool.to_string()),
+        Value::Number(n) => Node::Atom(number(n)),
+        Value::String(s) => Node::Atom(quote(s)?),
+        Value::Array(elements) => Node::Array(Items {
+            items: elements
+                .iter()
+                .map(|value| Ok(Item::new(None, value_node(value)?)))
+                .collect::<Result<_, String>>()?,
+            comments: Comments::new(),
+        }),
+        Value::Object(fields) => {
+            let mut keys = fields.keys().collect::<Vec<_>>();
+            keys.sort();
+
+            let items = keys
+                .into_iter()
+                .map(|key| {
+                    let node = value_node(&fields[key])?;
+                    let key = if is_ident(key) {
+                        key.clone()
+                    } else {
+                        quote(key)?
+                    };
+
+                    Ok(Item::new(Some(key), node))
+                })
+                .collect::<Result<_, String>>()?;
+
+            Node::Object(Items {
+                items,
+                comments: Comments::new(),
+            })
+        }
+    })
+}
+
+impl Values {
+    /// Writes the values in the config syntax, one key per line in
+    /// alphabetical order, which [`Values::from_str`] reads back as the same
+    /// values.
+    ///
+    /// Keys are annotated with their types, except for keys of type
+    /// [`Type::Any`] with a value and types that can't be written, like the
+    /// elements of an empty array inferred from data, which are left to be
+    /// inferred again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Err`] for keys that are not identifiers, keys without a
+    /// value nor a type, and values without syntax, like nulls or strings
+    /// with both kinds of quotes
+    pub fn to_config(&self) -> Result<String, ConfigError> {
+        let mut keys = self.type_map.keys().collect::<Vec<_>>();
+        keys.sort();
+
+        let mut document = Document::default();
+
+        for key in keys {
+            let unwritable = |message| ConfigError::Unwritable {
+                key: key.clone(),
+                message,
+            };
+
+            if !is_ident(key) {
+                return Err(unwritable("it is not a valid identifier".to_string()));
+            }
+
+            let typ = &self.type_map[key];
+            let value = self.value_map.get(key).filter(|value| !value.is_null());
+
+            let annotation = match (typ, value) {
+                (Type::Any, Some(_)) => None,
+                (typ, Some(_)) => type_node(typ).ok(),
+                (typ, None) => Some(type_node(typ).map_err(unwritable)?),
+            };
+
+            document.entries.push(Entry {
+                comments: Comments::new(),
+                key: key.clone(),
+                typ: annotation,
+                value: value.map(value_node).transpose().map_err(unwritable)?,
+                trailing: None,
+            });
+        }
+
+        Ok(document.write())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::values::{Format, Values};
+    use std::path::Path;
+
+    #[test]
+    fn round_trip() {
+        let config = r#"author: { age: Number, name: String } = { age: 30, name: "ana" }
+count = 3
+description: String
+flags: [Bool] = [true, FALSE]
+ratio: Number = -0.5
+servers: [{ host: String, port: Number }] = [{ host: 'say "hi"', port: 80 }, { host: "b", port: 8080 }, ]
+"#;
+        let values = Values::from_str(config, Path::new("config.tpl")).expect("Valid config");
+        let written = values.to_config().expect("Writable values");
+
+        assert_eq!(
+            written,
+            r#"author: { age: Number, name: String } = { age: 30, name: "ana" }
+count = 3
+description: String
+flags: [Bool] = [true, false]
+ratio: Number = -0.5
+servers: [{ host: String, port: Number }] = [
+    { host: 'say "hi"', port: 80 },
+    { host: "b", port: 8080 },
+]
+"#
+        );
+        assert_eq!(
+            Values::from_str(&written, Path::new("config.tpl")).expect("Valid config"),
+            values
+        );
+
+        let data = r#"{ "empty": [], "glob": { "*.rs": 1.0 }, "big": 18446744073709551615 }"#;
+        let values =
+            Values::from_data(data, Path::new("v.json"), Format::Json).expect("Valid JSON");
+        let written = values.to_config().expect("Writable values");
+        let read = Values::from_str(&written, Path::new("config.tpl")).expect("Valid config");
+
+        assert_eq!(read.value_map, values.value_map);
+        assert!(read.verify_types().is_ok());
+
+        let data = r#"{ "quotes": "'\"" }"#;
+        let values =
+            Values::from_data(data, Path::new("v.json"), Format::Json).expect("Valid JSON");
+        assert!(values.to_config().is_err());
+    }
+}
@@ -1,4 +1,4 @@
-set -l commands list new init deinit help create remove rm info debug-config
+set -l commands list new init deinit help create remove rm info debug-config fmt add update export import search install check
 set -l templates (temple list -se 2> /dev/null | tr " " "\n" || echo "")
 
 function __fish_temple_contains_temple_new
@@ -24,7 +24,7 @@ function __fish_temple_complete_templates
 end
 
 function __fish_temple_help_subcommand_completion
-    set -l commands list new init deinit help create remove rm info debug-config
+    set -l commands list new init deinit help create remove rm info debug-config fmt add update export import search install check
     set -l cmd_args (commandline -opc)
 
     if test (count $cmd_args) -eq 2
@@ -60,8 +60,16 @@ complete -c temple -n "not __fish_seen_subcommand_from $commands" -a info -d 'Ge
 complete -c temple -n "not __fish_seen_subcommand_from $commands" -a init -d 'Initialize a template config directory'
 complete -c temple -n "not __fish_seen_subcommand_from $commands" -a deinit -d 'Remove a temple config directory'
 complete -c temple -n "not __fish_seen_subcommand_from $commands" -a create -d 'Create a new empty template. You can then place files in it'
+complete -c temple -n "not __fish_seen_subcommand_from $commands" -a add -d 'Install a template from a git repository into the global config dir'
+complete -c temple -n "not __fish_seen_subcommand_from $commands" -a update -d 'Update templates installed with add from their recorded source'
+complete -c temple -n "not __fish_seen_subcommand_from $commands" -a search -d 'Search the templates listed in the registry index'
+complete -c temple -n "not __fish_seen_subcommand_from $commands" -a install -d 'Install a template listed in the registry index'
+complete -c temple -n "not __fish_seen_subcommand_from $commands" -a export -d 'Package a template as a .tar.gz or .zip archive'
+complete -c temple -n "not __fish_seen_subcommand_from $commands" -a import -d 'Install a template from a .tar.gz or .zip archive'
 complete -c temple -n "not __fish_seen_subcommand_from $commands" -a remove -d 'Remove an existing template'
 complete -c temple -n "not __fish_seen_subcommand_from $commands" -a rm -d 'Remove an existing template'
+complete -c temple -n "not __fish_seen_subcommand_from $commands" -a check -d 'Check a template for errors without rendering it'
+complete -c temple -n "not __fish_seen_subcommand_from $commands" -a fmt -d 'Format config files canonically, keeping their comments'
 complete -c temple -n "not __fish_seen_subcommand_from $commands" -a debug-config -d 'Parse and dump objects to stdout'
 complete -c temple -n "not __fish_seen_subcommand_from $commands" -a help -d 'Print this message or the help of the given subcommand(s)'
 
@@ -82,19 +90,49 @@ complete -c temple -n "__fish_seen_subcommand_from create; and __fish_seen_subco
 # remove
 complete -c temple -n "__fish_seen_subcommand_from remove rm; and not __fish_seen_subcommand_from help" -ka '(__fish_temple_complete_templates)'
 
+# add
+complete -c temple -n "__fish_seen_subcommand_from add" -s n -l name -r -d 'Name of the template'
+complete -c temple -n "__fish_seen_subcommand_from add" -s r -l rev -r -d 'Branch, tag or commit to check out'
+
+# update
+complete -c temple -n "__fish_seen_subcommand_from update; and not __fish_seen_subcommand_from help" -ka '(__fish_temple_complete_templates)'
+complete -c temple -n "__fish_seen_subcommand_from update" -s r -l rev -r -d 'Branch, tag or commit to pin the template to'
+complete -c temple -n "__fish_seen_subcommand_from update" -s f -l force -d 'Discard any local edits made to the template'
+
+# search, install
+complete -c temple -n "__fish_seen_subcommand_from search install" -l index -r -F -d 'Path or http:// url of the index'
+
+# export
+complete -c temple -n "__fish_seen_subcommand_from export; and not __fish_seen_subcommand_from help" -ka '(__fish_temple_complete_templates)'
+complete -c temple -n "__fish_seen_subcommand_from export" -s o -l output -r -F -d 'Path of the archive'
+
+# import
+complete -c temple -n "__fish_seen_subcommand_from import" -F
+complete -c temple -n "__fish_seen_subcommand_from import" -s l -l local -d 'Import into the local temple folder'
+complete -c temple -n "__fish_seen_subcommand_from import" -s o -l overwrite -d 'Overwrite a template with the same name if it exists'
+
 # debug
 complete -c temple -n "__fish_seen_subcommand_from debug-config" -F
 
+# fmt
+complete -c temple -n "__fish_seen_subcommand_from fmt" -F
+complete -c temple -n "__fish_seen_subcommand_from fmt" -l check -d "Don't write the files, fail if any is not formatted"
+
 # help
 complete -c temple -f -n "__fish_seen_subcommand_from help" -a "(__fish_temple_help_subcommand_completion)"
 
 # list
 complete -c temple -n "__fish_seen_subcommand_from list" -s s -l short -d 'Show templates in a single space separated list'
 complete -c temple -n "__fish_seen_subcommand_from list" -s p -l path -d 'Show templates path'
+complete -c temple -n "__fish_seen_subcommand_from list info debug-config" -l format -xa 'text json' -d 'Output format'
 
 # new
-complete -c temple -n "__fish_seen_subcommand_from new info; and not __fish_seen_subcommand_from help" -ka '(__fish_temple_complete_templates)'
+complete -c temple -n "__fish_seen_subcommand_from new info check; and not __fish_seen_subcommand_from help" -ka '(__fish_temple_complete_templates)'
 complete -c temple -n "__fish_seen_subcommand_from new" -s l -l local -d 'Prefer local (./.temple/template_name) if available [default: prefer ~/.temple/template_name]'
 complete -c temple -n "__fish_seen_subcommand_from new" -s i -l in-place -d 'Place contents in_place (./.) instead of creating a folder'
 complete -c temple -n "__fish_seen_subcommand_from new" -s o -l overwrite -d 'Overwrite any already existing files'
+complete -c temple -n "__fish_seen_subcommand_from new" -l no-hooks -d "Don't run the pre and post generation hooks of the template"
+complete -c temple -n "__fish_seen_subcommand_from new" -l trust -d 'Run the hooks of the template without asking for confirmation'
+complete -c temple -n "__fish_seen_subcommand_from new" -l values -r -F -d 'File with values in JSON, YAML, TOML or the config syntax'
+complete -c temple -n "__fish_seen_subcommand_from new" -s j -l jobs -r -d 'Number of files rendered in parallel [default: number of CPUs]'
 # complete -c temple -n "__fish_seen_subcommand_from new info; and __fish_seen_subcommand_from $templates" -n "not contains -- -- (commandline -opc)" -a '(__fish_temple_c_complete)' 